@@ -0,0 +1,215 @@
+use crate::history::History;
+use std::{
+    io::{self, Read, Write},
+    mem,
+};
+
+// Puts the terminal into raw mode until it is dropped, so that keys can be read as they are pressed
+// instead of a line at a time.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        // SAFETY: `termios` is plain data, and `tcgetattr` fills it in before it is used.
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original) };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    // Ctrl-C throws the line away, and Ctrl-D on an empty line ends the session.
+    Cancel,
+    EndOfInput,
+    // The terminal went away, so there won't be any more keys.
+    Closed,
+    Unknown,
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn read_key(input: &mut impl Read) -> io::Result<Key> {
+    let first = match read_byte(input)? {
+        Some(b) => b,
+        None => return Ok(Key::Closed),
+    };
+    Ok(match first {
+        b'\r' | b'\n' => Key::Enter,
+        127 | 8 => Key::Backspace,
+        1 => Key::Home,
+        5 => Key::End,
+        3 => Key::Cancel,
+        4 => Key::EndOfInput,
+        // Arrow keys and the like are sent as `ESC [` and then a letter, or a number and `~`.
+        27 => match (read_byte(input)?, read_byte(input)?) {
+            (Some(b'['), Some(b'A')) => Key::Up,
+            (Some(b'['), Some(b'B')) => Key::Down,
+            (Some(b'['), Some(b'C')) => Key::Right,
+            (Some(b'['), Some(b'D')) => Key::Left,
+            (Some(b'['), Some(b'H')) => Key::Home,
+            (Some(b'['), Some(b'F')) => Key::End,
+            (Some(b'['), Some(b'3')) => {
+                read_byte(input)?;
+                Key::Delete
+            }
+            _ => Key::Unknown,
+        },
+        b if b < 0x20 => Key::Unknown,
+        b => {
+            // The number of bytes in a UTF-8 character is the number of leading ones in its first
+            // byte, or one if there aren't any.
+            let len = (b.leading_ones() as usize).max(1);
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                bytes.extend(read_byte(input)?);
+            }
+            match std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        }
+    })
+}
+
+// The line being edited, and which line of the history is being shown in its place, if any.
+struct Line<'a> {
+    chars: Vec<char>,
+    cursor: usize,
+    history: &'a History,
+    recalled: Option<usize>,
+    // What was being written before going back through the history, to come back to at the end.
+    draft: Vec<char>,
+}
+
+impl Line<'_> {
+    fn recall(&mut self, i: Option<usize>) {
+        if self.recalled.is_none() {
+            self.draft = mem::take(&mut self.chars);
+        }
+        self.chars = match i {
+            Some(i) => self.history.get(i).unwrap_or_default().chars().collect(),
+            None => mem::take(&mut self.draft),
+        };
+        self.cursor = self.chars.len();
+        self.recalled = i;
+    }
+
+    fn edit(&mut self, key: Key) {
+        match key {
+            Key::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.chars.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.chars.len() => {
+                self.chars.remove(self.cursor);
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.chars.len(),
+            Key::Up => match self.recalled {
+                Some(i) if i > 0 => self.recall(Some(i - 1)),
+                None if self.history.len() > 0 => self.recall(Some(self.history.len() - 1)),
+                _ => {}
+            },
+            Key::Down => match self.recalled {
+                Some(i) if i + 1 < self.history.len() => self.recall(Some(i + 1)),
+                Some(_) => self.recall(None),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+fn redraw(out: &mut impl Write, prompt: &str, line: &Line) -> io::Result<()> {
+    let text: String = line.chars.iter().collect();
+    // Clears the rest of the line, and then moves back to the cursor from the start of the line.
+    write!(out, "\r{prompt}{text}\x1b[K\r")?;
+    let col = prompt.chars().count() + line.cursor;
+    if col > 0 {
+        write!(out, "\x1b[{col}C")?;
+    }
+    out.flush()
+}
+
+// Reads a line from the terminal, where the arrow keys move around it and go back through the
+// history. Gives back `None` at the end of the input.
+pub fn read_line(prompt: &str, history: &History) -> io::Result<Option<String>> {
+    let _raw = RawMode::enable()?;
+    let mut input = io::stdin().lock();
+    let mut out = io::stdout().lock();
+    let mut line = Line {
+        chars: Vec::new(),
+        cursor: 0,
+        history,
+        recalled: None,
+        draft: Vec::new(),
+    };
+    redraw(&mut out, prompt, &line)?;
+    loop {
+        match read_key(&mut input)? {
+            Key::Enter => {
+                write!(out, "\r\n")?;
+                return Ok(Some(line.chars.into_iter().collect()));
+            }
+            Key::Closed if line.chars.is_empty() => return Ok(None),
+            Key::Closed => return Ok(Some(line.chars.into_iter().collect())),
+            Key::EndOfInput if line.chars.is_empty() => {
+                write!(out, "\r\n")?;
+                return Ok(None);
+            }
+            Key::EndOfInput => line.edit(Key::Delete),
+            Key::Cancel => {
+                write!(out, "^C\r\n")?;
+                line.chars.clear();
+                line.cursor = 0;
+                line.recalled = None;
+            }
+            key => line.edit(key),
+        }
+        redraw(&mut out, prompt, &line)?;
+    }
+}
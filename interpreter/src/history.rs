@@ -0,0 +1,83 @@
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+// Only the most recent lines are kept, so that the file doesn't grow forever.
+const MAX_ENTRIES: usize = 1000;
+
+// The lines entered in the REPL, oldest first. They are saved to a dotfile, so that they can be
+// recalled in the next session too.
+#[derive(Debug, Default, PartialEq)]
+pub struct History {
+    entries: Vec<String>,
+}
+
+impl History {
+    // A missing file is the same as an empty history, since there won't be one the first time.
+    pub fn load(path: &Path) -> io::Result<History> {
+        let mut history = History::default();
+        match fs::read_to_string(path) {
+            Ok(saved) => saved.lines().for_each(|line| history.push(line)),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(history)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut saved = self.entries.join("\n");
+        saved.push('\n');
+        fs::write(path, saved)
+    }
+
+    // Blank lines and lines that are the same as the one before them aren't worth recalling.
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<&str> {
+        self.entries.get(i).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+// `~/.pale_history`, if there is a home directory to put it in.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".pale_history"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+
+    #[test]
+    fn test_history_round_trip() {
+        let path = std::env::temp_dir().join(format!("pale-history-{}", std::process::id()));
+        assert_eq!(History::load(&path).unwrap(), History::default());
+
+        let mut history = History::default();
+        for line in ["(+ 1 2)", "", "(print 3)", "(print 3)", "  (let x 4)  "] {
+            history.push(line);
+        }
+        history.save(&path).unwrap();
+        let loaded = History::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, history);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.get(0), Some("(+ 1 2)"));
+        assert_eq!(loaded.get(1), Some("(print 3)"));
+        assert_eq!(loaded.get(2), Some("(let x 4)"));
+    }
+}
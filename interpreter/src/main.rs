@@ -1,7 +1,21 @@
 #![allow(clippy::or_fun_call)]
 use clap::Parser;
-use pale::{run_lisp, run_lisp_dumped};
-use std::{error, fs};
+#[cfg(unix)]
+use history::History;
+use pale::{
+    check_lisp, make_program, run_lisp, run_lisp_all, run_lisp_dumped, tokenize, LispErrors,
+    LispType, Location, Scope, Var,
+};
+use std::{
+    error, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+mod editor;
+mod history;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
@@ -9,9 +23,23 @@ struct Args {
     #[clap(short = 'c', long = "command")]
     is_command: bool,
 
-    #[clap(short, long)]
+    /// Print the tokens and the syntax tree before running, for each line in the REPL.
+    #[clap(short, long, alias = "dump")]
     debug: bool,
 
+    /// Only parse the program and look for mistakes, without running it.
+    #[clap(long)]
+    check: bool,
+
+    /// Keep running the rest of the statements after one of them fails.
+    #[clap(long)]
+    keep_going: bool,
+
+    /// Print how long tokenizing, parsing and running the program took to stderr.
+    #[clap(long)]
+    time: bool,
+
+    /// A `.sul` file to run. Anything that isn't a file is run as source code.
     input: Option<String>,
 }
 
@@ -25,13 +53,32 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         }
     } else {
         if let Some(s) = args.input {
-            (fs::read_to_string(&s).unwrap(), s)
+            load_source(s)?
         } else {
-            // TODOOOOO: Running the interpreter off standard input.
-            return Err("Running in REPL mode is not yet implemented!".into());
+            return repl(args.debug);
         }
     };
-    if !args.debug {
+    if args.check {
+        check_lisp(&source, &file)?;
+    } else if args.keep_going {
+        let summary = run_lisp_all(&source, &file)?;
+        if summary.failed() > 0 {
+            eprintln!(
+                "{} of {} statements failed.",
+                summary.failed(),
+                summary.results.len()
+            );
+            return Err(summary.errors().into());
+        }
+    } else if args.time {
+        let mut timings = Vec::new();
+        let res = run_timed(&source, &file, &mut timings);
+        // Even if it failed, the phases that finished are worth knowing about.
+        for (phase, took) in timings {
+            eprintln!("{phase}: {took:?}");
+        }
+        res?;
+    } else if !args.debug {
         // Clap makes it true by default
         run_lisp(&source, &file)?;
     } else {
@@ -39,3 +86,154 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     }
     Ok(())
 }
+
+// Runs the program like `run_lisp`, and adds how long each phase took to `timings` as it finishes.
+fn run_timed(
+    source: &str,
+    file: &str,
+    timings: &mut Vec<(&'static str, Duration)>,
+) -> Result<Var, LispErrors> {
+    let start = Instant::now();
+    let toks = tokenize(source, file.to_string())?;
+    timings.push(("tokenize", start.elapsed()));
+
+    let start = Instant::now();
+    let location = Location {
+        filename: file.to_string(),
+        line: 0,
+        col: 0,
+    };
+    let program = make_program(&toks, &mut Scope::default(), &location)?;
+    timings.push(("parse", start.elapsed()));
+
+    let start = Instant::now();
+    let mut res = Var::new(LispType::Nil);
+    for statement in &program {
+        res = statement.resolve()?;
+    }
+    timings.push(("run", start.elapsed()));
+    Ok(res)
+}
+
+// Reads the program from `input` if it is a file (or looks like one), and gives back the source and
+// the name to report errors with.
+fn load_source(input: String) -> io::Result<(String, String)> {
+    if input.ends_with(".sul") || Path::new(&input).is_file() {
+        let source = fs::read_to_string(&input)
+            .map_err(|e| io::Error::new(e.kind(), format!("Could not read {input}: {e}")))?;
+        Ok((source, input))
+    } else {
+        Ok((input, "<provided>".to_string()))
+    }
+}
+
+// Runs each line of standard input as its own program and prints what it evaluates to, until EOF.
+// In a terminal, lines can be edited and earlier ones recalled with the arrow keys, and they are
+// saved to `~/.pale_history` for the next session.
+fn repl(dump: bool) -> Result<(), Box<dyn error::Error>> {
+    #[cfg(unix)]
+    if io::stdin().is_terminal() {
+        return repl_with_history(dump);
+    }
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        run_line(&line, dump);
+    }
+    println!();
+    Ok(())
+}
+
+#[cfg(unix)]
+fn repl_with_history(dump: bool) -> Result<(), Box<dyn error::Error>> {
+    let path = history::default_path();
+    // Not having the old history shouldn't keep anyone from using the REPL.
+    let mut history = match path.as_deref().map(History::load).transpose() {
+        Ok(history) => history.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Could not load the history: {e}");
+            History::default()
+        }
+    };
+    while let Some(line) = editor::read_line("> ", &history)? {
+        history.push(&line);
+        run_line(&line, dump);
+    }
+    if let Some(path) = path {
+        if let Err(e) = history.save(&path) {
+            eprintln!("Could not save the history to {}: {e}", path.display());
+        }
+    }
+    Ok(())
+}
+
+// Runs one line of the REPL and prints what it evaluates to.
+fn run_line(line: &str, dump: bool) {
+    if line.trim().is_empty() {
+        return;
+    }
+    let res = if dump {
+        run_lisp_dumped(line, "<repl>")
+    } else {
+        run_lisp(line, "<repl>")
+    };
+    match res {
+        Ok(value) => println!("{value}"),
+        // Mistakes shouldn't end the session.
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_source, run_timed, Args};
+    use clap::Parser;
+    use pale::run_lisp;
+    use std::fs;
+
+    #[test]
+    fn test_timing_does_not_change_the_result() {
+        let source = "(let x 4) (* x (+ 1 2))";
+        let mut timings = Vec::new();
+        let timed = run_timed(source, "-", &mut timings).unwrap();
+        assert_eq!(
+            timed.to_string(),
+            run_lisp(source, "-").unwrap().to_string()
+        );
+        let phases: Vec<_> = timings.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(phases, ["tokenize", "parse", "run"]);
+    }
+
+    #[test]
+    fn test_dump_is_the_same_as_debug() {
+        for flag in ["--dump", "--debug", "-d"] {
+            assert!(Args::try_parse_from(["pale", flag]).unwrap().debug);
+        }
+        assert!(!Args::try_parse_from(["pale"]).unwrap().debug);
+    }
+
+    #[test]
+    fn test_load_source() {
+        let path = std::env::temp_dir().join(format!("pale-test-{}.sul", std::process::id()));
+        fs::write(&path, "(+ 1 2)").unwrap();
+        let name = path.to_str().unwrap().to_string();
+        let (source, file) = load_source(name.clone()).unwrap();
+        assert_eq!(source, "(+ 1 2)");
+        assert_eq!(file, name);
+        fs::remove_file(&path).unwrap();
+
+        // Missing files are an error instead of being run as source code.
+        let err = load_source(name.clone()).unwrap_err();
+        assert!(err.to_string().contains(&name));
+
+        let (source, file) = load_source("(+ 1 2)".to_string()).unwrap();
+        assert_eq!(source, "(+ 1 2)");
+        assert_eq!(file, "<provided>");
+    }
+}
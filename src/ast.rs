@@ -1,49 +1,233 @@
 #![allow(clippy::or_fun_call)]
 
-use crate::callable::IntrinsicOp;
-use crate::error::LispErrors;
+use crate::callable::{
+    Callable, CaseLambda, DebugScope, DefTest, IntrinsicOp, LispFn, ReadLine, RunTests, TimeNow,
+};
+use crate::error::{LispErrors, ParseError};
 use crate::tokens::{KeyWord, Token, TokenType};
-use crate::types::LispType;
+use crate::types::{LispType, Numbers};
 use crate::Location;
 use std::{
-    cell::{Ref, RefCell, RefMut},
+    cell::{Cell, Ref, RefCell, RefMut},
     collections::BTreeMap,
     fmt::Display,
+    io::Write,
     rc::Rc,
 };
 
-#[derive(Debug, PartialEq)]
+// How deeply statements can be nested, while parsing and while running, before it is an error
+// instead of overflowing the stack. Debug builds use a lot more stack for each level, so this is
+// picked to fit in the main thread's stack even then.
+const DEFAULT_MAX_DEPTH: usize = 256;
+
+thread_local! {
+    static MAX_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_DEPTH) };
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// Changes the nesting limit for this thread.
+pub fn set_max_depth(depth: usize) {
+    MAX_DEPTH.with(|max| max.set(depth));
+}
+
+// Counts one level of nesting for as long as it is alive.
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter(loc: &Location) -> Result<Self, LispErrors> {
+        let max = MAX_DEPTH.with(Cell::get);
+        let depth = DEPTH.with(Cell::get);
+        if depth >= max {
+            return Err(LispErrors::new()
+                .error(loc, "Maximum nesting depth exceeded!")
+                .note(None, format!("Statements can only be nested {max} deep.")));
+        }
+        DEPTH.with(|d| d.set(depth + 1));
+        Ok(DepthGuard)
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(d.get() - 1));
+    }
+}
+
+#[derive(Debug)]
 pub struct Var {
     pub(crate) dat: Rc<RefCell<LispType>>,
 }
 
+// Pairs of values that are in the middle of being compared.
+pub(crate) type SeenPairs = Vec<(*const RefCell<LispType>, *const RefCell<LispType>)>;
+
 impl Display for Var {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", *self.get())
+        self.fmt_with(f, &mut Vec::new())
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct Statement {
+// Cloning a `Var` shares its value, like `new_ref`.
+impl Clone for Var {
+    fn clone(&self) -> Self {
+        self.new_ref()
+    }
+}
+
+impl PartialEq for Var {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_with(other, Numbers::Exact, &mut Vec::new())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
     pub(crate) args: Vec<Var>,
-    pub(crate) op: Var, // The inner value must be callable, so this won't panic (I hope)
+    // A function when it is parsed, but `set` can change that, so it is checked when it is called.
+    pub(crate) op: Var,
     pub(crate) res: RefCell<Option<Var>>,
     pub(crate) loc: Location,
+    // Whether running this always gives the same result, so that it only has to be run once.
+    pub(crate) pure: bool,
 }
 
 impl Statement {
-    pub(crate) fn resolve(&self) -> Result<Var, LispErrors> {
-        let r = self.op.get().unwrap_func().call(&self.args, &self.loc);
+    // `op` must be callable.
+    pub(crate) fn new(op: Var, args: Vec<Var>, loc: Location) -> Self {
+        // Only pure functions of values that can't change can be cached: a variable could be `set`.
+        let pure = op.get().unwrap_func().is_pure()
+            && args
+                .iter()
+                .all(|a| a.is_literal() || matches!(&*a.get(), LispType::Statement(s) if s.pure));
+        Statement {
+            args,
+            op,
+            res: RefCell::new(None),
+            loc,
+            pure,
+        }
+    }
+
+    pub fn resolve(&self) -> Result<Var, LispErrors> {
+        if self.pure {
+            // A copy, so that changing the result doesn't change the cache.
+            if let Some(res) = &*self.res.borrow() {
+                return Ok(Var::new(res.get().clone()));
+            }
+        }
+        let _depth = DepthGuard::enter(&self.loc)?;
+        self.check_arity()?;
+        let r = self.op.callable(&self.loc)?.call(&self.args, &self.loc);
         if let Ok(s) = &r {
             *self.res.borrow_mut() = Some(s.new_ref());
         }
         r
     }
+
+    // Looks for mistakes that can be found without running anything (like calling a function
+    // with the wrong number of arguments) in this statement and all of the statements inside it.
+    pub fn validate(&self) -> Result<(), LispErrors> {
+        let mut errs = LispErrors::new();
+        self.collect_diagnostics(&mut errs);
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
+    }
+
+    // Calls `visitor` on this statement and then on every statement inside it, in the order they
+    // are written.
+    pub fn walk(&self, visitor: &mut impl FnMut(&Statement)) {
+        visitor(self);
+        for arg in &self.args {
+            arg.walk(visitor);
+        }
+    }
+
+    // Replaces the statements inside this one that only do pure arithmetic or comparisons on
+    // literals with their results, so that they aren't worked out every time this is run.
+    // Anything that uses a variable or calls something impure is left alone.
+    pub fn const_fold(&mut self) {
+        for arg in &mut self.args {
+            let folded = match &mut *arg.get_mut() {
+                LispType::Statement(s) => s.fold_into_value(),
+                _ => None,
+            };
+            if let Some(value) = folded {
+                *arg = value;
+            }
+        }
+    }
+
+    fn fold_into_value(&mut self) -> Option<Var> {
+        self.const_fold();
+        let foldable = match self.op.callable(&self.loc) {
+            Ok(f) => f.is_foldable() && f.arity().allows(self.args.len()),
+            Err(_) => false,
+        } && self.args.iter().all(Var::is_literal);
+        if !foldable {
+            return None;
+        }
+        // Errors (like dividing by zero) are left to be reported when the program is run.
+        self.resolve().ok()
+    }
+
+    // Every function is given the right number of arguments, so they all report it the same way.
+    fn check_arity(&self) -> Result<(), LispErrors> {
+        let arity = self.op.callable(&self.loc)?.arity();
+        if arity.allows(self.args.len()) {
+            return Ok(());
+        }
+        Err(LispErrors::new().error(
+            &self.loc,
+            format!(
+                "This function takes {arity}, but {} were given!",
+                self.args.len()
+            ),
+        ))
+    }
+
+    fn collect_diagnostics(&self, errs: &mut LispErrors) {
+        if let Err(e) = self.check_arity() {
+            errs.extend(e);
+        }
+        for arg in &self.args {
+            arg.collect_diagnostics(errs);
+        }
+    }
+}
+
+// A value that is only worked out the first time it is forced, made by `lazy`.
+// Copies share the result, so the expression still only runs once.
+#[derive(Debug, Clone)]
+pub struct Promise {
+    expr: Var,
+    res: Rc<RefCell<Option<Var>>>,
+}
+
+impl Promise {
+    pub(crate) fn new(expr: Var) -> Self {
+        Self {
+            expr,
+            res: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    // Runs the expression the first time, and gives back the same result every time after that.
+    pub(crate) fn force(&self) -> Result<Var, LispErrors> {
+        if let Some(res) = &*self.res.borrow() {
+            return Ok(res.new_ref());
+        }
+        let res = self.expr.resolve()?;
+        *self.res.borrow_mut() = Some(res.new_ref());
+        Ok(res)
+    }
 }
 
 #[allow(dead_code)]
 impl Var {
-    pub(crate) fn new<T: Into<LispType>>(i: T) -> Var {
+    pub fn new<T: Into<LispType>>(i: T) -> Var {
         Var {
             dat: Rc::new(RefCell::new(i.into())),
         }
@@ -53,26 +237,199 @@ impl Var {
             dat: Rc::clone(&self.dat),
         }
     }
-    pub(crate) fn get(&self) -> Ref<LispType> {
+    pub fn get(&self) -> Ref<'_, LispType> {
         self.dat.borrow()
     }
-    pub(crate) fn get_mut(&self) -> RefMut<LispType> {
+    pub(crate) fn get_mut(&self) -> RefMut<'_, LispType> {
         self.dat.borrow_mut()
     }
+    // The function in this variable. Calling it through `get` would keep the variable borrowed
+    // while it runs, so it would panic if the call `set` the variable. It is an error instead of a
+    // panic if the variable isn't a function, since `set` could have changed it into something else.
+    pub(crate) fn callable(&self, loc: &Location) -> Result<Rc<dyn Callable>, LispErrors> {
+        match &*self.get() {
+            LispType::Func(f) => Ok(Rc::clone(f)),
+            other => Err(LispErrors::new().error(
+                loc,
+                format!("Only functions can be called, not {}!", other.type_name()),
+            )),
+        }
+    }
     pub(crate) fn resolve(&self) -> Result<Self, LispErrors> {
         match &*self.dat.borrow() {
             LispType::Statement(s) => s.resolve(),
             _ => Ok(self.new_ref()),
         }
     }
+    // Like `resolve`, but also resolves the statements inside lists, however deeply they are
+    // nested, so there are no statements left in the result. Lists that contain themselves are
+    // not supported, and will recurse until the stack overflows.
+    pub fn resolve_deep(&self) -> Result<Self, LispErrors> {
+        let res = self.resolve()?;
+        let items = match &*res.get() {
+            LispType::List(items) => items.clone(),
+            _ => return Ok(res.new_ref()),
+        };
+        let items = items
+            .iter()
+            .map(Var::resolve_deep)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Var::new(LispType::List(items)))
+    }
+    pub(crate) fn fmt_with(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        seen: &mut Vec<*const RefCell<LispType>>,
+    ) -> std::fmt::Result {
+        let ptr = Rc::as_ptr(&self.dat);
+        if seen.contains(&ptr) {
+            return write!(f, "...");
+        }
+        seen.push(ptr);
+        let res = self.get().fmt_with(f, seen);
+        seen.pop();
+        res
+    }
+    pub(crate) fn eq_with(&self, other: &Var, numbers: Numbers, seen: &mut SeenPairs) -> bool {
+        let pair = (Rc::as_ptr(&self.dat), Rc::as_ptr(&other.dat));
+        if pair.0 == pair.1 || seen.contains(&pair) {
+            return true;
+        }
+        seen.push(pair);
+        let eq = self.get().eq_with(&other.get(), numbers, seen);
+        seen.pop();
+        eq
+    }
+    // Calls `visitor` on every statement in this value, including the ones inside lists.
+    pub fn walk(&self, visitor: &mut impl FnMut(&Statement)) {
+        match &*self.get() {
+            LispType::Statement(s) => s.walk(visitor),
+            LispType::List(items) => {
+                for item in items {
+                    item.walk(visitor);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn collect_diagnostics(&self, errs: &mut LispErrors) {
+        match &*self.get() {
+            LispType::Statement(s) => s.collect_diagnostics(errs),
+            LispType::List(items) => {
+                for item in items {
+                    item.collect_diagnostics(errs);
+                }
+            }
+            _ => {}
+        }
+    }
+    // Whether this is a value written straight into the source. Variables share their value with
+    // the scope, so they are never literals.
+    fn is_literal(&self) -> bool {
+        Rc::strong_count(&self.dat) == 1
+            && matches!(
+                *self.get(),
+                LispType::Integer(_)
+                    | LispType::Floating(_)
+                    | LispType::Str(_)
+                    | LispType::Bool(_)
+                    | LispType::Char(_)
+                    | LispType::Nil
+            )
+    }
     pub(crate) fn unwrap(self) -> LispType {
         Rc::try_unwrap(self.dat).unwrap().into_inner()
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct Scope {
+// Copies share the values of all of their variables. Names that aren't bound in a scope are looked
+// up in its parent, so a function's scope only has to hold its parameters.
+#[derive(Debug, Clone)]
+pub struct Scope {
     pub(crate) vars: BTreeMap<String, Var>,
+    pub(crate) parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    // A scope without anything bound in it, not even the builtins that `Scope::default` has.
+    pub fn empty() -> Scope {
+        Scope {
+            vars: BTreeMap::new(),
+            parent: None,
+        }
+    }
+    // A scope with only the named builtins from `Scope::default`, for running programs that
+    // shouldn't be able to do everything. Names that aren't builtins are left out.
+    pub fn with_builtins(names: &[&str]) -> Scope {
+        let mut all = Scope::default();
+        let vars = names
+            .iter()
+            .filter_map(|name| all.vars.remove_entry(*name))
+            .collect();
+        Scope { vars, parent: None }
+    }
+    // An empty scope inside `parent`.
+    pub(crate) fn child(parent: Rc<Scope>) -> Scope {
+        Scope {
+            vars: BTreeMap::new(),
+            parent: Some(parent),
+        }
+    }
+    // A copy of this scope, which shares the values of all of its variables.
+    pub(crate) fn snapshot(&self) -> Scope {
+        Scope {
+            vars: self
+                .vars
+                .iter()
+                .map(|(name, value)| (name.clone(), value.new_ref()))
+                .collect(),
+            parent: self.parent.clone(),
+        }
+    }
+    // Looks for `name` in this scope, and then in each parent going outward.
+    pub(crate) fn get(&self, name: &str) -> Option<&Var> {
+        match self.vars.get(name) {
+            Some(value) => Some(value),
+            None => self.parent.as_ref().and_then(|p| p.get(name)),
+        }
+    }
+    // Every binding that can be seen from this scope, where inner bindings hide outer ones.
+    fn visible(&self) -> BTreeMap<&str, &Var> {
+        let mut all = match &self.parent {
+            Some(p) => p.visible(),
+            None => BTreeMap::new(),
+        };
+        all.extend(self.vars.iter().map(|(name, value)| (name.as_str(), value)));
+        all
+    }
+    // Binds a function written in Rust, so that programs run in this scope can call it.
+    pub fn register(&mut self, name: &str, f: impl Callable + 'static) {
+        self.vars.insert(name.to_string(), Var::new(f));
+    }
+    // Every binding and its value, one per line.
+    pub(crate) fn dump(&self) -> String {
+        self.visible()
+            .iter()
+            .map(|(name, value)| format!("{name} = {value}\n"))
+            .collect()
+    }
+    // The same as `Scope::default`, except that `run-tests` writes its results to `out` instead of
+    // stdout.
+    pub fn with_test_output<W: Write + 'static>(out: W) -> Scope {
+        let mut scope = Scope::default();
+        let tests = Rc::new(RefCell::new(Vec::new()));
+        scope.register("deftest", DefTest::new(Rc::clone(&tests)));
+        scope.register("run-tests", RunTests::with_writer(tests, out));
+        scope
+    }
+    // All of the names bound in this scope that start with `prefix`, in sorted order.
+    pub fn completions(&self, prefix: &str) -> Vec<String> {
+        self.visible()
+            .range(prefix..)
+            .map(|(name, _)| name.to_string())
+            .take_while(|name| name.starts_with(prefix))
+            .collect()
+    }
 }
 
 impl std::default::Default for Scope {
@@ -82,13 +439,81 @@ impl std::default::Default for Scope {
             ("+", IntrinsicOp::Add),
             ("-", IntrinsicOp::Subtract),
             ("*", IntrinsicOp::Multiply),
+            ("/", IntrinsicOp::Divide),
+            ("%", IntrinsicOp::Modulo("%")),
+            ("deep-equal", IntrinsicOp::DeepEqual),
+            ("integer?", IntrinsicOp::IsInteger),
+            ("type-of", IntrinsicOp::TypeOf),
+            ("str", IntrinsicOp::ToStr),
+            ("parse-int", IntrinsicOp::ParseInt),
+            ("gensym", IntrinsicOp::Gensym),
+            ("float?", IntrinsicOp::IsFloat),
+            ("strlen", IntrinsicOp::StrLen),
+            ("byte-length", IntrinsicOp::ByteLength),
+            ("partition", IntrinsicOp::Partition),
+            ("quotient", IntrinsicOp::Quotient),
+            ("remainder", IntrinsicOp::Remainder),
+            ("modulo", IntrinsicOp::Modulo("modulo")),
+            ("bit-and", IntrinsicOp::BitAnd),
+            ("bit-or", IntrinsicOp::BitOr),
+            ("bit-xor", IntrinsicOp::BitXor),
+            ("bit-not", IntrinsicOp::BitNot),
+            ("shl", IntrinsicOp::ShiftLeft),
+            ("shr", IntrinsicOp::ShiftRight),
+            ("interleave", IntrinsicOp::Interleave),
+            ("to-list", IntrinsicOp::ToList),
+            ("split-at", IntrinsicOp::SplitAt),
+            ("wrap-text", IntrinsicOp::WrapText),
+            ("car", IntrinsicOp::Car),
+            ("cdr", IntrinsicOp::Cdr),
+            ("cons", IntrinsicOp::Cons),
+            ("cadr", IntrinsicOp::Cadr),
+            ("caddr", IntrinsicOp::Caddr),
+            ("cddr", IntrinsicOp::Cddr),
+            ("cdar", IntrinsicOp::Cdar),
+            ("clamp", IntrinsicOp::Clamp),
+            ("min", IntrinsicOp::Min),
+            ("max", IntrinsicOp::Max),
+            ("abs", IntrinsicOp::Abs),
+            ("sqrt", IntrinsicOp::Sqrt),
+            ("=", IntrinsicOp::Equal),
+            ("<", IntrinsicOp::Less),
+            (">", IntrinsicOp::Greater),
+            ("<=", IntrinsicOp::LessEqual),
+            (">=", IntrinsicOp::GreaterEqual),
+            ("spread", IntrinsicOp::Spread),
+            ("map", IntrinsicOp::Map),
+            ("filter", IntrinsicOp::Filter),
+            ("format-number", IntrinsicOp::FormatNumber),
+            ("lazy", IntrinsicOp::Lazy),
+            ("force", IntrinsicOp::Force),
+            ("starts-with?", IntrinsicOp::StartsWith),
+            ("ends-with?", IntrinsicOp::EndsWith),
+            ("assert", IntrinsicOp::Assert),
+            ("list", IntrinsicOp::List),
+            ("memoize", IntrinsicOp::Memoize),
+            ("not", IntrinsicOp::Not),
+            ("and", IntrinsicOp::And),
+            ("or", IntrinsicOp::Or),
+            ("do", IntrinsicOp::Do),
+            ("concat", IntrinsicOp::Concat),
+            ("len", IntrinsicOp::Len),
+            ("nth", IntrinsicOp::Nth),
+            ("char-at", IntrinsicOp::CharAt),
         ];
-        Scope {
-            vars: items
-                .into_iter()
-                .map(|x| (x.0.to_string(), Var::new(x.1)))
-                .collect(),
-        }
+        let mut vars: BTreeMap<String, Var> = items
+            .into_iter()
+            .map(|x| (x.0.to_string(), Var::new(x.1)))
+            .collect();
+        vars.insert("time-now".to_string(), Var::new(TimeNow::default()));
+        vars.insert("read-line".to_string(), Var::new(ReadLine::default()));
+        let tests = Rc::new(RefCell::new(Vec::new()));
+        vars.insert(
+            "deftest".to_string(),
+            Var::new(DefTest::new(Rc::clone(&tests))),
+        );
+        vars.insert("run-tests".to_string(), Var::new(RunTests::new(tests)));
+        Scope { vars, parent: None }
     }
 }
 
@@ -107,6 +532,23 @@ struct AstParser<'a> {
 enum AstParserStatus {
     Normal,
     Identifiers(usize, Vec<usize>),
+    // `(let x 5)`, where everything after the `let` at this index is names and values.
+    BareBindings(usize),
+    // `(fn name (params) body)`, where everything after the `fn` at this index is the definition.
+    Function(usize),
+    // `(case-lambda name ((params) body) ...)`, where the cases come after the `case-lambda` at
+    // this index.
+    CaseLambda(usize),
+    // `(define (name params) body)`, where everything after the `define` at this index is the
+    // definition.
+    Define(usize),
+    // `(quote datum)`, where the datum comes after the `quote` at this index.
+    Quote(usize),
+    // `(cond (test value) ...)`, where the clauses come after the `cond` at this index.
+    Cond(usize),
+    // `(with-redefs ((name value) ...) body)`, where the bindings come after the `with-redefs` at
+    // this index.
+    WithRedefs(usize),
 }
 
 #[derive(Debug)]
@@ -140,7 +582,7 @@ impl<'a> AstParser<'a> {
     ) -> Result<(), LispErrors> {
         let value = value.unwrap_or(Var::new(LispType::Nil));
         let ident = ident.to_string();
-        if self.idents.vars.contains_key(&ident) {
+        if self.idents.get(&ident).is_some() {
             //TODO(#12): Shadowing
             return Err(LispErrors::new()
                 .error(loc, "Shadowing is not currently allowed!")
@@ -150,7 +592,82 @@ impl<'a> AstParser<'a> {
         Ok(())
     }
 
+    // A binding list without any parentheses is read as alternating names and values if it has a
+    // literal in it, like `(x 1 y 2)`, or if every value is a name that is already bound, like
+    // `(x y)` when `y` is. Otherwise each binding is either a bare name (bound to nil) or a
+    // parenthesized name and value, like `((x 1) (y 2))`.
+    fn is_flat_binding_list(&self, tokens: &[Token]) -> bool {
+        if !tokens
+            .iter()
+            .all(|t| matches!(t.dat, TokenType::Ident(_) | TokenType::Recognizable(_)))
+        {
+            return false;
+        }
+        let has_literal = tokens
+            .iter()
+            .any(|t| matches!(t.dat, TokenType::Recognizable(_)));
+        // Bare names that are already bound couldn't be introduced again anyway, since shadowing
+        // isn't allowed, so they have to be values.
+        let values_are_bound =
+            !tokens.is_empty()
+                && tokens.len().is_multiple_of(2)
+                && tokens.iter().skip(1).step_by(2).all(
+                    |t| matches!(&t.dat, TokenType::Ident(id) if self.idents.get(id).is_some()),
+                );
+        has_literal || values_are_bound
+    }
+
+    // Returns the value that was bound last, if there were any bindings.
+    fn process_flat_identifiers(&mut self, tokens: &[Token]) -> Result<Option<Var>, LispErrors> {
+        let mut to_introduce: Vec<(&str, Option<Var>, &Location)> = Vec::new();
+        for pair in tokens.chunks(2) {
+            let name = match &pair[0].dat {
+                TokenType::Ident(name) => name,
+                _ => {
+                    return Err(
+                        LispErrors::new().error(&pair[0].loc, "Cannot assign to literal value!")
+                    )
+                }
+            };
+            let value = match pair.get(1).map(|t| &t.dat) {
+                Some(TokenType::Recognizable(value)) => Var::new(value.clone()),
+                Some(TokenType::Ident(id)) => match self.idents.get(id) {
+                    Some(s) => s.new_ref(),
+                    None => {
+                        return Err(ParseError::UnknownIdent {
+                            name: id.clone(),
+                            loc: pair[1].loc.clone(),
+                        }
+                        .into())
+                    }
+                },
+                Some(_) => {
+                    return Err(LispErrors::new().error(
+                        &pair[1].loc,
+                        "Variables must be literals or other values (not expressions)!",
+                    ))
+                }
+                None => {
+                    return Err(LispErrors::new()
+                        .error(&pair[0].loc, format!("Variable {name:?} has no value!"))
+                        .note(None, "Names and values have to come in pairs."))
+                }
+            };
+            to_introduce.push((name, Some(value), &pair[0].loc));
+        }
+        let last = to_introduce
+            .last()
+            .and_then(|(_, value, _)| value.as_ref().map(Var::new_ref));
+        for (ident, value, loc) in to_introduce {
+            self.introduce_identifier(ident, value, loc)?;
+        }
+        Ok(last)
+    }
+
     fn process_identifiers(&mut self, tokens: &[Token]) -> Result<(), LispErrors> {
+        if self.is_flat_binding_list(tokens) {
+            return self.process_flat_identifiers(tokens).map(|_| ());
+        }
         let mut to_introduce: Vec<(&str, Option<Var>, &Location)> = Vec::new();
         let mut status = IdentParserStatus::Normal;
         for tok in tokens {
@@ -198,10 +715,13 @@ impl<'a> AstParser<'a> {
                         ident: Some(new_id),
                         has_value: false,
                     },
-                ) => match self.idents.vars.get(id.as_str()) {
+                ) => match self.idents.get(id) {
                     None => {
-                        return Err(LispErrors::new()
-                            .error(&tok.loc, format!("Unknown identifier {id:?}!")))
+                        return Err(ParseError::UnknownIdent {
+                            name: id.clone(),
+                            loc: tok.loc.clone(),
+                        }
+                        .into())
                     }
                     Some(s) => {
                         to_introduce.push((new_id, Some(s.new_ref()), &tok.loc));
@@ -303,6 +823,10 @@ impl<'a> AstParser<'a> {
                         .note(&tok.loc, "Delete it."));
                 }
                 (TokenType::EndStmt, _) => unreachable!(),
+                (TokenType::OpenBracket | TokenType::CloseBracket, _) => {
+                    return Err(LispErrors::new()
+                        .error(&tok.loc, "Lists are not allowed in variable assignments!"))
+                }
                 (TokenType::Recognizable(_), IdentParserStatus::Normal) => {
                     return Err(LispErrors::new()
                         .error(&tok.loc, "Unknown literal in `let` statement.")
@@ -327,44 +851,129 @@ impl<'a> AstParser<'a> {
         Ok(())
     }
 
-    fn parse(mut self) -> Result<Statement, LispErrors> {
-        if self.ts.len() < 2 {
-            return Err(LispErrors::new().error(self.start, "Empty statements are not allowed!"));
-        }
-        let mut start_idx = 0;
-        if let TokenType::StartStmt = self.ts[start_idx].dat {
-            start_idx = 1;
-        }
-        let mut end_idx = self.ts.len() - 1;
-        if let TokenType::EndStmt = self.ts[end_idx].dat {
-            end_idx -= 1;
-        }
-        if start_idx > end_idx {
-            return Err(LispErrors::new().error(self.start, "Empty statements are not allowed!"));
+    // Keywords that act like functions can't be looked up in the scope, so they are added here.
+    fn push_keyword_op(&mut self, op: Var, i: usize) {
+        self.args.push(op);
+        if self.loc.is_none() {
+            self.loc = Some(self.ts[i].loc.clone());
         }
+    }
+
+    fn parse_args(&mut self, start_idx: usize, end_idx: usize) -> Result<(), LispErrors> {
         for i in start_idx..=end_idx {
             match (&mut self.status, &self.ts[i].dat) {
-                (AstParserStatus::Normal, TokenType::StartStmt) => {
+                (AstParserStatus::Normal, TokenType::StartStmt | TokenType::OpenBracket) => {
                     self.open_stack.push(i);
                 }
-                (AstParserStatus::Normal, TokenType::EndStmt) => {
+                (AstParserStatus::Normal, TokenType::EndStmt | TokenType::CloseBracket) => {
+                    let closes_list = self.ts[i].dat == TokenType::CloseBracket;
                     if let Some(o) = self.open_stack.pop() {
+                        let opens_list = self.ts[o].dat == TokenType::OpenBracket;
+                        if opens_list != closes_list {
+                            return Err(LispErrors::new()
+                                .error(
+                                    &self.ts[i].loc,
+                                    if closes_list {
+                                        "Closing bracket does not match the opening parenthesis!"
+                                    } else {
+                                        "Closing parenthesis does not match the opening bracket!"
+                                    },
+                                )
+                                .note(&self.ts[o].loc, "Opened here."));
+                        }
                         if self.open_stack.is_empty() {
-                            self.args.push(Var::new(make_ast(
-                                &self.ts[o..=i],
-                                self.idents,
-                                &self.ts[o + 1].loc,
-                            )?));
+                            if opens_list {
+                                self.args.push(Var::new(make_list(
+                                    &self.ts[o..=i],
+                                    self.idents,
+                                    &self.ts[o].loc,
+                                )?));
+                            } else {
+                                self.args.push(Var::new(make_ast(
+                                    &self.ts[o..=i],
+                                    self.idents,
+                                    &self.ts[o + 1].loc,
+                                )?));
+                            }
                         }
                     } else {
-                        return Err(LispErrors::new()
-                            .error(&self.ts[i].loc, "Unmatched closing parentheses!")
-                            .note(None, "Delete it."));
+                        return Err(LispErrors::from(ParseError::UnmatchedParen {
+                            loc: self.ts[i].loc.clone(),
+                            paren: if closes_list { ']' } else { ')' },
+                        })
+                        .note(None, "Delete it."));
                     }
                 }
                 (AstParserStatus::Normal, TokenType::KeyWord(word)) => match word {
+                    // A nested `let` is handled when its own statement is parsed.
                     KeyWord::Let => {
-                        self.status = AstParserStatus::Identifiers(i, Vec::new());
+                        if self.open_stack.is_empty() {
+                            self.status = match self.ts.get(i + 1).map(|t| &t.dat) {
+                                Some(TokenType::StartStmt) => {
+                                    AstParserStatus::Identifiers(i, Vec::new())
+                                }
+                                _ => AstParserStatus::BareBindings(i),
+                            };
+                        }
+                    }
+                    KeyWord::LetRec => {
+                        if self.open_stack.is_empty() {
+                            if self.ts.get(i + 1).map(|t| &t.dat) != Some(&TokenType::StartStmt) {
+                                return Err(LispErrors::new()
+                                    .error(&self.ts[i].loc, "`letrec` needs a list of bindings!")
+                                    .note(None, "Try `(letrec ((f (fn (x) x))) f 1)`."));
+                            }
+                            self.status = AstParserStatus::Identifiers(i, Vec::new());
+                        }
+                    }
+                    // This needs to know what is in scope where it's written, so it's a keyword
+                    // instead of a function in `Scope::default`.
+                    KeyWord::DebugScope => {
+                        if self.open_stack.is_empty() {
+                            let op = Var::new(DebugScope::new(self.idents.snapshot()));
+                            self.push_keyword_op(op, i);
+                        }
+                    }
+                    KeyWord::If => {
+                        if self.open_stack.is_empty() {
+                            self.push_keyword_op(Var::new(IntrinsicOp::If), i);
+                        }
+                    }
+                    KeyWord::Set => {
+                        if self.open_stack.is_empty() {
+                            self.check_set_target(i, end_idx)?;
+                            self.push_keyword_op(Var::new(IntrinsicOp::Set), i);
+                        }
+                    }
+                    KeyWord::Fn => {
+                        if self.open_stack.is_empty() {
+                            self.status = AstParserStatus::Function(i);
+                        }
+                    }
+                    KeyWord::CaseLambda => {
+                        if self.open_stack.is_empty() {
+                            self.status = AstParserStatus::CaseLambda(i);
+                        }
+                    }
+                    KeyWord::Define => {
+                        if self.open_stack.is_empty() {
+                            self.status = AstParserStatus::Define(i);
+                        }
+                    }
+                    KeyWord::Quote => {
+                        if self.open_stack.is_empty() {
+                            self.status = AstParserStatus::Quote(i);
+                        }
+                    }
+                    KeyWord::Cond => {
+                        if self.open_stack.is_empty() {
+                            self.status = AstParserStatus::Cond(i);
+                        }
+                    }
+                    KeyWord::WithRedefs => {
+                        if self.open_stack.is_empty() {
+                            self.status = AstParserStatus::WithRedefs(i);
+                        }
                     }
                 },
                 (AstParserStatus::Normal, TokenType::Recognizable(n)) => {
@@ -372,18 +981,26 @@ impl<'a> AstParser<'a> {
                         self.args.push(Var::new(n.clone()));
                     }
                 }
-                (AstParserStatus::Normal, TokenType::Ident(id)) => match self.idents.vars.get(id) {
-                    None => {
-                        return Err(LispErrors::new()
-                            .error(&self.ts[i].loc, format!("Unknown identifier `{id}`!")))
-                    }
-                    Some(s) => {
-                        if self.open_stack.is_empty() {
+                // Identifiers in nested statements are looked up when those are parsed, so that they
+                // can see bindings made by a `let` earlier in this statement.
+                (AstParserStatus::Normal, TokenType::Ident(id)) if self.open_stack.is_empty() => {
+                    match self.idents.get(id) {
+                        None => {
+                            return Err(ParseError::UnknownIdent {
+                                name: id.clone(),
+                                loc: self.ts[i].loc.clone(),
+                            }
+                            .into())
+                        }
+                        Some(s) => {
                             self.args.push(s.new_ref());
-                            self.loc = Some(self.ts[i].loc.clone());
+                            // Errors from a call are reported where the function was named, not at its last argument.
+                            if self.loc.is_none() {
+                                self.loc = Some(self.ts[i].loc.clone());
+                            }
                         }
                     }
-                },
+                }
                 (AstParserStatus::Identifiers(_, positions), TokenType::StartStmt) => {
                     positions.push(i)
                 }
@@ -391,20 +1008,496 @@ impl<'a> AstParser<'a> {
                     positions.pop();
                     if positions.is_empty() {
                         let t = *start; // For some reason this is required for the borrow checker to allow it.
-                        self.process_identifiers(&self.ts[t + 2..i])?;
+                        if self.ts[t].dat == TokenType::KeyWord(KeyWord::LetRec) {
+                            self.process_rec_bindings(t + 2, i - 1)?;
+                        } else {
+                            self.process_identifiers(&self.ts[t + 2..i])?;
+                        }
                         self.status = AstParserStatus::Normal;
                     }
                 }
                 (_, _) => {}
             }
         }
-        if !self.open_stack.is_empty() {
+        if let Some(o) = self.open_stack.pop() {
+            return Err(LispErrors::from(ParseError::UnmatchedParen {
+                loc: self.ts[o].loc.clone(),
+                paren: if self.ts[o].dat == TokenType::OpenBracket {
+                    '['
+                } else {
+                    '('
+                },
+            })
+            .note(None, "Deleting it might fix this error."));
+        }
+        if let AstParserStatus::BareBindings(start) = self.status {
+            // `(let x 5)` binds `x` in the scope it is written in and evaluates to the value bound.
+            match self.process_flat_identifiers(&self.ts[start + 1..=end_idx])? {
+                Some(value) => {
+                    self.push_keyword_op(Var::new(IntrinsicOp::Identity), start);
+                    self.args.push(value);
+                }
+                None => {
+                    return Err(LispErrors::new()
+                        .error(&self.ts[start].loc, "`let` needs something to bind!")
+                        .note(None, "Try `(let x 5)`."))
+                }
+            }
+            self.status = AstParserStatus::Normal;
+        }
+        if let AstParserStatus::Quote(start) = self.status {
+            let datum = self.quote(start, end_idx)?;
+            self.push_keyword_op(Var::new(IntrinsicOp::Identity), start);
+            self.args.push(datum);
+            self.status = AstParserStatus::Normal;
+        }
+        if let AstParserStatus::Cond(start) = self.status {
+            let clauses = self.process_cond(start, end_idx)?;
+            self.push_keyword_op(Var::new(IntrinsicOp::Cond), start);
+            self.args.extend(clauses);
+            self.status = AstParserStatus::Normal;
+        }
+        if let AstParserStatus::WithRedefs(start) = self.status {
+            let parts = self.process_with_redefs(start, end_idx)?;
+            self.push_keyword_op(Var::new(IntrinsicOp::WithRedefs), start);
+            self.args.extend(parts);
+            self.status = AstParserStatus::Normal;
+        }
+        if let AstParserStatus::Function(start) = self.status {
+            let f = self.process_function(start, end_idx)?;
+            self.push_keyword_op(Var::new(IntrinsicOp::Identity), start);
+            self.args.push(f);
+            self.status = AstParserStatus::Normal;
+        }
+        if let AstParserStatus::CaseLambda(start) = self.status {
+            let f = self.process_case_lambda(start, end_idx)?;
+            self.push_keyword_op(Var::new(IntrinsicOp::Identity), start);
+            self.args.push(f);
+            self.status = AstParserStatus::Normal;
+        }
+        if let AstParserStatus::Define(start) = self.status {
+            self.process_define(start, end_idx)?;
+            self.push_keyword_op(Var::new(IntrinsicOp::Identity), start);
+            self.args.push(Var::new(LispType::Nil));
+            self.status = AstParserStatus::Normal;
+        }
+        Ok(())
+    }
+
+    // Each clause of a `cond` is a test and a value, which are parsed like any other arguments.
+    // `(else value)` is the same as `(true value)`. The tests and values are given back in order.
+    fn process_cond(&mut self, start: usize, end_idx: usize) -> Result<Vec<Var>, LispErrors> {
+        let ts = self.ts;
+        let mut parts = Vec::new();
+        let mut i = start + 1;
+        while i <= end_idx {
+            if ts[i].dat != TokenType::StartStmt {
+                return Err(LispErrors::new()
+                    .error(&ts[i].loc, "Each `cond` clause must be in parentheses!")
+                    .note(None, "Try `(cond ((= x 1) \"one\") (else \"other\"))`."));
+            }
+            let close = closing_paren(ts, i, end_idx).unwrap_or(i);
+            let clause = &ts[i..=close];
+            let is_else = matches!(&clause[1].dat, TokenType::Ident(name) if name == "else");
+            let mut parser = AstParser::new(clause, self.idents, &clause[0].loc);
+            let first = if is_else { 2 } else { 1 };
+            if first < clause.len() - 1 {
+                parser.parse_args(first, clause.len() - 2)?;
+            }
+            let mut clause_parts = parser.args;
+            if is_else {
+                clause_parts.insert(0, Var::new(true));
+            }
+            if clause_parts.len() != 2 {
+                return Err(LispErrors::new()
+                    .error(&clause[0].loc, "A `cond` clause needs a test and a value!")
+                    .note(None, "Try `((= x 1) \"one\")`."));
+            }
+            parts.extend(clause_parts);
+            i = close + 1;
+        }
+        Ok(parts)
+    }
+
+    // `(with-redefs ((f stub) (x 5)) body)` runs the body with `f` and `x` changed to the new values,
+    // and then changes them back. The names have to be bound already, like with `set`. The
+    // variables and values are given back in pairs, followed by the body.
+    fn process_with_redefs(
+        &mut self,
+        start: usize,
+        end_idx: usize,
+    ) -> Result<Vec<Var>, LispErrors> {
+        let ts = self.ts;
+        let usage = "Try `(with-redefs ((f (fn (x) 0))) f 1)`.";
+        let bindings_end = match ts.get(start + 1) {
+            Some(tok) if start < end_idx && tok.dat == TokenType::StartStmt => {
+                closing_paren(ts, start + 1, end_idx)
+            }
+            _ => None,
+        };
+        let Some(bindings_end) = bindings_end else {
+            return Err(LispErrors::new()
+                .error(&ts[start].loc, "`with-redefs` needs a list of bindings!")
+                .note(None, usage));
+        };
+        let mut parts = Vec::new();
+        let mut i = start + 2;
+        while i < bindings_end {
+            let close = closing_paren(ts, i, bindings_end - 1)
+                .filter(|&close| ts[i].dat == TokenType::StartStmt && close > i + 2);
+            let name = match (close, &ts[i + 1].dat) {
+                (Some(_), TokenType::Ident(name)) => name,
+                _ => {
+                    return Err(LispErrors::new()
+                        .error(&ts[i].loc, "Each binding must be a name and a value!")
+                        .note(None, usage))
+                }
+            };
+            let close = close.unwrap();
+            let Some(var) = self.idents.get(name).map(Var::new_ref) else {
+                return Err(LispErrors::new()
+                    .error(
+                        &ts[i + 1].loc,
+                        format!("Cannot redefine `{name}`, because it isn't bound!"),
+                    )
+                    .note(None, "Use `let` to make a new variable."));
+            };
+            let binding = &ts[i..=close];
+            let mut parser = AstParser::new(binding, self.idents, &binding[0].loc);
+            parser.parse_args(2, binding.len() - 2)?;
+            if parser.args.len() != 1 {
+                return Err(LispErrors::new()
+                    .error(&ts[i].loc, "Each binding must be a name and a value!")
+                    .note(None, usage));
+            }
+            parts.push(var);
+            parts.extend(parser.args);
+            i = close + 1;
+        }
+        let body = &ts[bindings_end + 1..=end_idx];
+        if body.is_empty() {
+            return Err(LispErrors::new()
+                .error(&ts[start].loc, "`with-redefs` needs a body!")
+                .note(None, usage));
+        }
+        parts.push(Var::new(make_ast(body, self.idents, &body[0].loc)?));
+        Ok(parts)
+    }
+
+    // `(set x value)` changes the value of `x` everywhere it is used, so `x` has to already be
+    // bound. The name and value are then parsed like any other arguments.
+    fn check_set_target(&self, i: usize, end_idx: usize) -> Result<(), LispErrors> {
+        match self.ts.get(i + 1).map(|t| &t.dat) {
+            Some(TokenType::Ident(name)) if i < end_idx => {
+                if self.idents.get(name).is_some() {
+                    Ok(())
+                } else {
+                    Err(LispErrors::new()
+                        .error(
+                            &self.ts[i + 1].loc,
+                            format!("Cannot set `{name}`, because it isn't bound!"),
+                        )
+                        .note(None, "Use `let` to make a new variable."))
+                }
+            }
+            _ => {
+                Err(LispErrors::new().error(&self.ts[i].loc, "`set` needs the name of a variable!"))
+            }
+        }
+    }
+
+    // Turns the tokens after a `quote` into data instead of code: statements become lists and names
+    // become symbols. There has to be exactly one thing after the `quote`.
+    fn quote(&self, start: usize, end_idx: usize) -> Result<Var, LispErrors> {
+        let mut stack: Vec<Vec<Var>> = vec![Vec::new()];
+        for tok in &self.ts[start + 1..=end_idx] {
+            let value = match &tok.dat {
+                TokenType::StartStmt | TokenType::OpenBracket => {
+                    stack.push(Vec::new());
+                    continue;
+                }
+                TokenType::EndStmt | TokenType::CloseBracket => {
+                    if stack.len() == 1 {
+                        return Err(LispErrors::from(ParseError::UnmatchedParen {
+                            loc: tok.loc.clone(),
+                            paren: ')',
+                        })
+                        .note(None, "Delete it."));
+                    }
+                    LispType::List(stack.pop().unwrap())
+                }
+                TokenType::Ident(name) => LispType::Symbol(name.clone()),
+                TokenType::KeyWord(word) => LispType::Symbol(word.to_string()),
+                TokenType::Recognizable(value) => value.clone(),
+            };
+            stack.last_mut().unwrap().push(Var::new(value));
+        }
+        if stack.len() > 1 {
+            return Err(ParseError::UnmatchedParen {
+                loc: self.ts[start].loc.clone(),
+                paren: '(',
+            }
+            .into());
+        }
+        let mut data = stack.pop().unwrap();
+        if data.len() != 1 {
+            return Err(LispErrors::new().error(
+                &self.ts[start].loc,
+                format!(
+                    "`quote` takes exactly 1 thing to quote, but {} were given!",
+                    data.len()
+                ),
+            ));
+        }
+        Ok(data.remove(0))
+    }
+
+    // Makes the function defined by `(fn name (params) body)` (or `(fn (params) body)`, which has no
+    // name) and binds it to its name. A named function can see itself, so it can be recursive.
+    fn process_function(&mut self, start: usize, end_idx: usize) -> Result<Var, LispErrors> {
+        let mut i = start + 1;
+        let name = match self.ts.get(i).map(|t| &t.dat) {
+            Some(TokenType::Ident(name)) if i <= end_idx => {
+                i += 1;
+                Some(name.as_str())
+            }
+            _ => None,
+        };
+        if i > end_idx || self.ts[i].dat != TokenType::StartStmt {
+            return Err(LispErrors::new()
+                .error(&self.ts[start].loc, "`fn` needs a list of parameters!")
+                .note(None, "Try `(fn square (x) (* x x))`."));
+        }
+        self.make_function(name, i + 1, start, end_idx)
+    }
+
+    // `(case-lambda name ((x) x) ((x y) (+ x y)))` is a function with a case for each number of
+    // arguments. Like `fn`, the name can be left out, and each case can call the whole function by
+    // its name.
+    fn process_case_lambda(&mut self, start: usize, end_idx: usize) -> Result<Var, LispErrors> {
+        let ts = self.ts;
+        let mut i = start + 1;
+        let f = Var::new(LispType::Nil);
+        if let Some(TokenType::Ident(name)) = ts.get(i).filter(|_| i <= end_idx).map(|t| &t.dat) {
+            self.introduce_identifier(name, Some(f.new_ref()), &ts[i].loc)?;
+            i += 1;
+        }
+        let mut cases = Vec::new();
+        while i <= end_idx {
+            let close = closing_paren(ts, i, end_idx).filter(|_| {
+                ts[i].dat == TokenType::StartStmt && ts[i + 1].dat == TokenType::StartStmt
+            });
+            let Some(close) = close else {
+                return Err(LispErrors::new()
+                    .error(
+                        &ts[i].loc,
+                        "Each `case-lambda` case must be parameters and a body!",
+                    )
+                    .note(None, "Try `(case-lambda ((x) x) ((x y) (+ x y)))`."));
+            };
+            cases.push(self.make_function(None, i + 2, start, close - 1)?);
+            i = close + 1;
+        }
+        if cases.is_empty() {
+            return Err(LispErrors::new()
+                .error(&ts[start].loc, "`case-lambda` needs at least one case!")
+                .note(None, "Try `(case-lambda ((x) x) ((x y) (+ x y)))`."));
+        }
+        *f.get_mut() = CaseLambda::new(cases).into();
+        Ok(f)
+    }
+
+    // The bindings of a `letrec`, between `first` and `last`, are names and functions, like
+    // `(even? (fn (n) ...))`. Every name is bound before any of the functions are made, so that
+    // they can all call each other.
+    fn process_rec_bindings(&mut self, first: usize, last: usize) -> Result<(), LispErrors> {
+        let ts = self.ts;
+        let mut bindings = Vec::new();
+        let mut i = first;
+        while i <= last {
+            let close = closing_paren(ts, i, last);
+            let binding = match (close, ts.get(i + 1), ts.get(i + 2), ts.get(i + 3)) {
+                (Some(close), Some(name), Some(open), Some(keyword)) if i + 3 < close => {
+                    match (&ts[i].dat, &name.dat, &open.dat, &keyword.dat) {
+                        (
+                            TokenType::StartStmt,
+                            TokenType::Ident(name),
+                            TokenType::StartStmt,
+                            TokenType::KeyWord(KeyWord::Fn),
+                        ) if closing_paren(ts, i + 2, last) == Some(close - 1) => {
+                            Some((name.as_str(), i + 3, close - 2))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match binding {
+                Some(binding) => bindings.push(binding),
+                None => {
+                    return Err(LispErrors::new()
+                        .error(&ts[i].loc, "`letrec` can only bind names to functions!")
+                        .note(None, "Try `(f (fn (x) x))`."))
+                }
+            }
+            i = closing_paren(ts, i, last).unwrap_or(last) + 1;
+        }
+        let mut vars = Vec::with_capacity(bindings.len());
+        for &(name, start, _) in &bindings {
+            let var = Var::new(LispType::Nil);
+            self.introduce_identifier(name, Some(var.new_ref()), &ts[start - 2].loc)?;
+            vars.push(var);
+        }
+        for (&(_, start, end), var) in bindings.iter().zip(vars) {
+            let f = self.process_function(start, end)?;
+            let f = f.get().clone();
+            *var.get_mut() = f;
+        }
+        Ok(())
+    }
+
+    // `(define (name params) body)` is the same as `(fn name (params) body)`, but is nil instead of
+    // the function.
+    fn process_define(&mut self, start: usize, end_idx: usize) -> Result<(), LispErrors> {
+        let head = (self.ts.get(start + 1), self.ts.get(start + 2));
+        let name = match head {
+            (Some(open), Some(name)) if start + 2 <= end_idx => match (&open.dat, &name.dat) {
+                (TokenType::StartStmt, TokenType::Ident(name)) => name.as_str(),
+                _ => "",
+            },
+            _ => "",
+        };
+        if name.is_empty() {
             return Err(LispErrors::new()
                 .error(
-                    &self.ts[self.open_stack.pop().unwrap()].loc,
-                    "Unmatched opening parentheses!",
+                    &self.ts[start].loc,
+                    "`define` needs a name and parameters in parentheses!",
                 )
+                .note(None, "Try `(define (square x) (* x x))`."));
+        }
+        self.make_function(Some(name), start + 3, start, end_idx)?;
+        Ok(())
+    }
+
+    // Makes the function for the keyword at `start`, with its parameters starting at `i` and
+    // ending with a closing parenthesis, which is followed by the body.
+    fn make_function(
+        &mut self,
+        name: Option<&str>,
+        mut i: usize,
+        start: usize,
+        end_idx: usize,
+    ) -> Result<Var, LispErrors> {
+        let mut params: Vec<String> = Vec::new();
+        loop {
+            match self.ts.get(i).filter(|_| i <= end_idx).map(|t| &t.dat) {
+                Some(TokenType::EndStmt) => break,
+                Some(TokenType::Ident(param)) => {
+                    if params.contains(param) {
+                        return Err(LispErrors::new().error(
+                            &self.ts[i].loc,
+                            format!("The parameter `{param}` is given more than once!"),
+                        ));
+                    }
+                    params.push(param.clone());
+                }
+                Some(_) => {
+                    return Err(
+                        LispErrors::new().error(&self.ts[i].loc, "Parameters must be names!")
+                    )
+                }
+                None => {
+                    return Err(ParseError::UnmatchedParen {
+                        loc: self.ts[start].loc.clone(),
+                        paren: '(',
+                    }
+                    .into())
+                }
+            }
+            i += 1;
+        }
+        let body = &self.ts[i + 1..=end_idx];
+        if body.is_empty() {
+            let keyword = match &self.ts[start].dat {
+                TokenType::KeyWord(word) => word.to_string(),
+                _ => "fn".to_string(),
+            };
+            return Err(
+                LispErrors::new().error(&self.ts[start].loc, format!("`{keyword}` needs a body!"))
+            );
+        }
+        let mut scope = self.idents.snapshot();
+        let f = Var::new(LispType::Nil);
+        if let Some(name) = name {
+            scope.vars.insert(name.to_string(), f.new_ref());
+        }
+        *f.get_mut() = LispFn::new(params, body.to_vec(), Rc::new(scope)).into();
+        if let Some(name) = name {
+            self.introduce_identifier(name, Some(f.new_ref()), &self.ts[start + 1].loc)?;
+        }
+        Ok(f)
+    }
+
+    fn parse(mut self) -> Result<Statement, LispErrors> {
+        // A single value without any parentheses, like the body of `(fn get-x () x)`.
+        if let [tok] = self.ts {
+            let paren = match tok.dat {
+                TokenType::StartStmt => Some('('),
+                TokenType::EndStmt => Some(')'),
+                _ => None,
+            };
+            if let Some(paren) = paren {
+                return Err(ParseError::UnmatchedParen {
+                    loc: tok.loc.clone(),
+                    paren,
+                }
+                .into());
+            } else {
+                self.loc = Some(tok.loc.clone());
+                self.parse_args(0, 0)?;
+                return Ok(Statement::new(
+                    Var::new(IntrinsicOp::Identity),
+                    self.args,
+                    self.loc.unwrap(),
+                ));
+            }
+        }
+        if self.ts.len() < 2 {
+            return Err(ParseError::EmptyStatement {
+                loc: self.start.clone(),
+            }
+            .into());
+        }
+        let mut start_idx = 0;
+        if let TokenType::StartStmt = self.ts[start_idx].dat {
+            start_idx = 1;
+            // Otherwise, the statement would just end with the tokens, like `(+ 1 2`.
+            if !closes_first_paren(self.ts) {
+                return Err(LispErrors::from(ParseError::UnmatchedParen {
+                    loc: self.ts[0].loc.clone(),
+                    paren: '(',
+                })
                 .note(None, "Deleting it might fix this error."));
+            }
+        }
+        let mut end_idx = self.ts.len() - 1;
+        if let TokenType::EndStmt = self.ts[end_idx].dat {
+            end_idx -= 1;
+        }
+        // `()` is the empty list.
+        if start_idx > end_idx {
+            return Ok(Statement::new(
+                Var::new(IntrinsicOp::Identity),
+                vec![Var::new(LispType::List(Vec::new()))],
+                self.ts[0].loc.clone(),
+            ));
+        }
+        self.parse_args(start_idx, end_idx)?;
+        if self.args.is_empty() {
+            return Err(ParseError::EmptyStatement {
+                loc: self.start.clone(),
+            }
+            .into());
         }
         let s = self.args.remove(0);
         if let LispType::Func(_) = *s.get() {
@@ -413,22 +1506,154 @@ impl<'a> AstParser<'a> {
             return Err(LispErrors::new()
                 .error(self.start, "Raw lists are not available (Yet...)!")
                 .note(None, "This is not a function.")
-                .note(None, "Use the `list` intrinsic to convert this to a list."));
+                .note(
+                    None,
+                    "Use brackets (`[1 2 3]`) or `list` (`(list 1 2 3)`) to make a list.",
+                ));
+        }
+        Ok(Statement::new(s, self.args, self.loc.unwrap()))
+    }
+
+    // Parses a bracketed list literal, like `[1 2 3]`. Unlike a statement, the first element does not
+    // need to be callable, and the list can be empty.
+    fn parse_list(mut self) -> Result<LispType, LispErrors> {
+        // The brackets are the first and last tokens, so only the tokens between them are parsed.
+        if self.ts.len() > 2 {
+            self.parse_args(1, self.ts.len() - 2)?;
+        }
+        Ok(LispType::List(self.args))
+    }
+}
+
+// The index of the parenthesis that closes the one at `open`, if it is closed by `end_idx`.
+fn closing_paren(ts: &[Token], open: usize, end_idx: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, tok) in ts.iter().enumerate().take(end_idx + 1).skip(open) {
+        match tok.dat {
+            TokenType::StartStmt => depth += 1,
+            TokenType::EndStmt => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(i);
         }
-        Ok(Statement {
-            args: self.args,
-            op: s,
-            res: RefCell::new(None),
-            loc: self.loc.unwrap(),
-        })
     }
+    None
 }
 
-pub(crate) fn make_ast(
+// Whether the parenthesis that `ts` starts with is closed somewhere in `ts`.
+fn closes_first_paren(ts: &[Token]) -> bool {
+    let mut depth = 0usize;
+    for tok in ts {
+        match tok.dat {
+            TokenType::StartStmt => depth += 1,
+            TokenType::EndStmt => {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+pub fn make_ast(
     ts: &[Token],
     idents: &mut Scope,
     start: &Location,
 ) -> Result<Statement, LispErrors> {
+    let _depth = DepthGuard::enter(start)?;
     let ast_parser = AstParser::new(ts, idents, start);
     ast_parser.parse()
 }
+
+// Parses every top-level statement in `ts`, in order. They are all parsed in `idents`, so later ones
+// can use what earlier ones bind.
+pub fn make_program(
+    ts: &[Token],
+    idents: &mut Scope,
+    start: &Location,
+) -> Result<Vec<Statement>, LispErrors> {
+    if ts.is_empty() {
+        return Ok(vec![make_ast(ts, idents, start)?]);
+    }
+    let mut statements = Vec::new();
+    let mut i = 0;
+    while i < ts.len() {
+        let mut end = group_end(ts, i);
+        // A `$` at the top level applies to the values written just before it, so `print $ + 1 2`
+        // is read as `(print (+ 1 2))`.
+        if end == i {
+            let mut next = i + 1;
+            while next < ts.len() && !is_paren(&ts[next]) {
+                next += 1;
+            }
+            if next < ts.len() && opened_by_dollar(ts, next) {
+                end = group_end(ts, next);
+                let mut wrapped = Vec::with_capacity(end - i + 3);
+                wrapped.push(Token {
+                    dat: TokenType::StartStmt,
+                    ..ts[i].clone()
+                });
+                wrapped.extend_from_slice(&ts[i..=end]);
+                wrapped.push(Token {
+                    loc: ts[end].end.clone(),
+                    end: ts[end].end.clone(),
+                    dat: TokenType::EndStmt,
+                });
+                statements.push(make_ast(&wrapped, idents, &ts[i].loc)?);
+                i = end + 1;
+                continue;
+            }
+        }
+        statements.push(make_ast(&ts[i..=end], idents, &ts[i].loc)?);
+        i = end + 1;
+    }
+    Ok(statements)
+}
+
+// A statement is a single value, or everything up to the parenthesis or bracket that closes the
+// one it starts with. If that is never closed, it is the rest of the tokens.
+fn group_end(ts: &[Token], start: usize) -> usize {
+    let mut depth = 0usize;
+    for (j, tok) in ts.iter().enumerate().skip(start) {
+        match tok.dat {
+            TokenType::StartStmt | TokenType::OpenBracket => depth += 1,
+            TokenType::EndStmt | TokenType::CloseBracket => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 {
+            return j;
+        }
+    }
+    ts.len() - 1
+}
+
+fn is_paren(tok: &Token) -> bool {
+    matches!(
+        tok.dat,
+        TokenType::StartStmt
+            | TokenType::EndStmt
+            | TokenType::OpenBracket
+            | TokenType::CloseBracket
+    )
+}
+
+// The statements that `$` starts are closed by a parenthesis that doesn't take up any space, since
+// it isn't in the source.
+fn opened_by_dollar(ts: &[Token], open: usize) -> bool {
+    matches!(ts[open].dat, TokenType::StartStmt)
+        && closing_paren(ts, open, ts.len() - 1).is_some_and(|close| ts[close].loc == ts[close].end)
+}
+
+pub(crate) fn make_list(
+    ts: &[Token],
+    idents: &mut Scope,
+    start: &Location,
+) -> Result<LispType, LispErrors> {
+    let _depth = DepthGuard::enter(start)?;
+    let ast_parser = AstParser::new(ts, idents, start);
+    ast_parser.parse_list()
+}
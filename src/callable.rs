@@ -1,104 +1,1848 @@
+use crate::ast::{make_ast, Promise, Scope};
 use crate::error::LispErrors;
+use crate::tokens::Token;
 use crate::types::LispType;
 use crate::Location;
 use crate::Var;
-use std::fmt::Debug;
-pub trait Callable: Debug {
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Display};
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{SystemTime, UNIX_EPOCH};
+pub trait Callable: Debug + CallableClone {
     fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors>;
+    // How many arguments `call` accepts, used to catch mistakes before anything is run.
+    fn arity(&self) -> Arity {
+        Arity::ANY
+    }
+    // Whether calling this with the same arguments always gives the same result without doing
+    // anything else, so that it can be worked out ahead of time.
+    fn is_pure(&self) -> bool {
+        false
+    }
+    // Whether `Statement::const_fold` may work this out ahead of time when every argument is a
+    // literal. Only pure arithmetic and comparisons are, so folding never changes what a program
+    // prints or which errors it reports.
+    fn is_foldable(&self) -> bool {
+        false
+    }
+    // If calling this just gives back one of its arguments (like the branch that an `if` picks),
+    // runs whatever it needs to pick it and gives it back without resolving it. Functions use this
+    // to loop instead of recursing when they call themselves as the last thing they do.
+    fn tail_arg(&self, _args: &[Var], _loc_called: &Location) -> Result<Option<Var>, LispErrors> {
+        Ok(None)
+    }
+}
+
+// Lets functions be cloned even though they are used as trait objects. Anything that is `Clone` gets
+// this for free.
+pub trait CallableClone {
+    fn clone_box(&self) -> Box<dyn Callable>;
+}
+
+impl<T: Callable + Clone + 'static> CallableClone for T {
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Callable> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub const ANY: Arity = Arity { min: 0, max: None };
+    pub fn exactly(n: usize) -> Self {
+        Arity {
+            min: n,
+            max: Some(n),
+        }
+    }
+    pub fn at_least(n: usize) -> Self {
+        Arity { min: n, max: None }
+    }
+    pub fn allows(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        match self.max {
+            Some(max) if max == self.min => write!(f, "exactly {max} {}", plural(max)),
+            Some(max) => write!(f, "between {} and {max} arguments", self.min),
+            None => write!(f, "at least {} {}", self.min, plural(self.min)),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum IntrinsicOp {
     Add,
     Subtract,
     Print,
     Multiply,
+    DeepEqual,
+    IsInteger,
+    IsFloat,
+    StrLen,
+    ByteLength,
+    Partition,
+    Quotient,
+    Remainder,
+    // The name it was bound to, since it is both `modulo` and `%`.
+    Modulo(&'static str),
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
+    Interleave,
+    ToList,
+    SplitAt,
+    WrapText,
+    Divide,
+    Car,
+    Cdr,
+    Cons,
+    Cadr,
+    Caddr,
+    Cddr,
+    Cdar,
+    If,
+    Cond,
+    Clamp,
+    Equal,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Spread,
+    FormatNumber,
+    Lazy,
+    Force,
+    StartsWith,
+    EndsWith,
+    Concat,
+    Len,
+    Nth,
+    CharAt,
+    Do,
+    TypeOf,
+    ToStr,
+    ParseInt,
+    Gensym,
+    Map,
+    Filter,
+    Min,
+    Max,
+    Abs,
+    Sqrt,
+    Assert,
+    List,
+    Memoize,
+    Not,
+    And,
+    Or,
+    // Only made by the `set` keyword, which makes sure the first argument is a variable.
+    Set,
+    // Only made by the `with-redefs` keyword. The arguments are pairs of a variable and the value
+    // it has while the last argument runs.
+    WithRedefs,
+    // Not in `Scope::default`; the parser uses it for statements that are just a value, like `(let x 5)`.
+    Identity,
+}
+
+fn expect_args(
+    name: &str,
+    args: &[Var],
+    count: usize,
+    loc_called: &Location,
+) -> Result<(), LispErrors> {
+    if args.len() != count {
+        let plural = if count == 1 { "" } else { "s" };
+        return Err(LispErrors::new().error(
+            loc_called,
+            format!(
+                "`{name}` requires exactly {count} argument{plural}, but {} were given!",
+                args.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn expect_at_least(
+    name: &str,
+    args: &[Var],
+    count: usize,
+    loc_called: &Location,
+) -> Result<(), LispErrors> {
+    if args.len() < count {
+        let plural = if count == 1 { "" } else { "s" };
+        return Err(LispErrors::new().error(
+            loc_called,
+            format!(
+                "`{name}` requires at least {count} argument{plural}, but {} were given!",
+                args.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn expect_int(name: &str, arg: &Var, loc_called: &Location) -> Result<isize, LispErrors> {
+    match *arg.resolve()?.get() {
+        LispType::Integer(i) => Ok(i),
+        ref other => Err(LispErrors::new().error(
+            loc_called,
+            format!("`{name}` requires integers, not {}!", other.type_name()),
+        )),
+    }
+}
+
+fn expect_str(name: &str, arg: &Var, loc_called: &Location) -> Result<String, LispErrors> {
+    match &*arg.resolve()?.get() {
+        LispType::Str(s) => Ok(s.clone()),
+        other => Err(LispErrors::new().error(
+            loc_called,
+            format!("`{name}` requires strings, not {}!", other.type_name()),
+        )),
+    }
+}
+
+// Resolves to a function, so it's safe to call `unwrap_func` on the result.
+fn expect_func(name: &str, arg: &Var, loc_called: &Location) -> Result<Var, LispErrors> {
+    let f = arg.resolve()?;
+    if let LispType::Func(_) = *f.get() {
+    } else {
+        return Err(LispErrors::new().error(
+            loc_called,
+            format!("`{name}` requires a function, not {}!", f.get().type_name()),
+        ));
+    }
+    Ok(f)
+}
+
+// The items of a list, which are not resolved yet.
+fn expect_list(name: &str, arg: &Var, loc_called: &Location) -> Result<Vec<Var>, LispErrors> {
+    match &*arg.resolve()?.get() {
+        LispType::List(items) => Ok(items.iter().map(Var::new_ref).collect()),
+        other => Err(LispErrors::new().error(
+            loc_called,
+            format!("`{name}` requires a list, not {}!", other.type_name()),
+        )),
+    }
+}
+
+// The first item of a list.
+fn car(name: &str, list: &Var, loc_called: &Location) -> Result<Var, LispErrors> {
+    match expect_list(name, list, loc_called)?.into_iter().next() {
+        Some(first) => Ok(first),
+        None => Err(LispErrors::new().error(
+            loc_called,
+            format!("`{name}` was given a list that is too short!"),
+        )),
+    }
+}
+
+// Everything but the first item of a list.
+fn cdr(name: &str, list: &Var, loc_called: &Location) -> Result<Var, LispErrors> {
+    let items = expect_list(name, list, loc_called)?;
+    if items.is_empty() {
+        return Err(LispErrors::new().error(
+            loc_called,
+            format!("`{name}` was given a list that is too short!"),
+        ));
+    }
+    Ok(Var::new(LispType::List(
+        items.into_iter().skip(1).collect(),
+    )))
+}
+
+// Breaks lines on spaces so that none of them are longer than `width` characters, unless a single
+// word is longer than that. Existing line breaks are kept.
+fn wrap_text(text: &str, width: usize) -> String {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut line_len = 0;
+        for word in paragraph.split_whitespace() {
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > width {
+                lines.push(std::mem::take(&mut line));
+                line_len = 0;
+            }
+            if line_len > 0 {
+                line.push(' ');
+                line_len += 1;
+            }
+            line.push_str(word);
+            line_len += word_len;
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+// Puts `separator` between every group of three digits in the integer part of a formatted number,
+// like `-1234567.5` => `-1,234,567.5`.
+fn group_digits(number: &str, separator: &str) -> String {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", number),
+    };
+    let (whole, fraction) = match unsigned.find('.') {
+        Some(i) => unsigned.split_at(i),
+        None => (unsigned, ""),
+    };
+    let mut grouped = String::from(sign);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(fraction);
+    grouped
+}
+
+// The resolved arguments of an arithmetic operation. The result is a float if and only if they
+// are floats, so a whole float is never turned back into an integer.
+enum Numbers {
+    Integers(Vec<isize>),
+    Floats(Vec<f64>),
+}
+
+impl Numbers {
+    fn into_floats(self) -> Vec<f64> {
+        match self {
+            Numbers::Integers(nums) => nums.into_iter().map(|i| i as f64).collect(),
+            Numbers::Floats(nums) => nums,
+        }
+    }
+}
+
+// Resolves arguments that must all be numbers. If any of them are floats, the integers are
+// converted to floats too, so that integers and floats can be mixed freely.
+fn numeric_args(
+    args: &[Var],
+    operation: &str,
+    loc_called: &Location,
+) -> Result<Numbers, LispErrors> {
+    let mut ints = Vec::with_capacity(args.len());
+    let mut floats = Vec::with_capacity(args.len());
+    let mut any_floats = false;
+    for (i, a) in args.iter().enumerate() {
+        let value = a.resolve()?;
+        let value = value.get();
+        match (&*value, value.as_f64()) {
+            (&LispType::Integer(i), Some(f)) => {
+                ints.push(i);
+                floats.push(f);
+            }
+            (_, Some(f)) => {
+                any_floats = true;
+                floats.push(f);
+            }
+            (other, None) => {
+                let err = LispErrors::new().error(
+                    loc_called,
+                    format!(
+                        "Incompatible types for {operation}: number and {} (argument {i})",
+                        other.type_name()
+                    ),
+                );
+                // Only statements remember where they were written.
+                return Err(match &*a.get() {
+                    LispType::Statement(s) => err.note(&s.loc, "That argument comes from here."),
+                    _ => err,
+                });
+            }
+        }
+    }
+    if any_floats {
+        Ok(Numbers::Floats(floats))
+    } else {
+        Ok(Numbers::Integers(ints))
+    }
+}
+
+// Combines the numbers from left to right, erroring instead of overflowing. It is `None` if there
+// aren't any numbers.
+fn checked_fold(
+    nums: &[isize],
+    op: fn(isize, isize) -> Option<isize>,
+    symbol: &str,
+    loc_called: &Location,
+) -> Result<Option<isize>, LispErrors> {
+    let Some((&first, rest)) = nums.split_first() else {
+        return Ok(None);
+    };
+    let mut acc = first;
+    for &n in rest {
+        acc = op(acc, n).ok_or_else(|| {
+            LispErrors::new().error(
+                loc_called,
+                format!("Overflow when working out {acc} {symbol} {n}!"),
+            )
+        })?;
+    }
+    Ok(Some(acc))
+}
+
+// The two integer arguments of a division, erroring if the divisor is zero.
+fn division_args(
+    name: &str,
+    args: &[Var],
+    loc_called: &Location,
+) -> Result<(isize, isize), LispErrors> {
+    expect_args(name, args, 2, loc_called)?;
+    let dividend = expect_int(name, &args[0], loc_called)?;
+    let divisor = expect_int(name, &args[1], loc_called)?;
+    if divisor == 0 {
+        return Err(LispErrors::new().error(loc_called, format!("`{name}` by zero!")));
+    }
+    Ok((dividend, divisor))
+}
+
+impl IntrinsicOp {
+    // The argument that `if`, `cond`, `do` and `identity` give back, after running what comes
+    // before it.
+    fn pick_tail(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        match self {
+            IntrinsicOp::If => {
+                if !self.arity().allows(args.len()) {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "`if` requires a condition, a branch for when it is true, and optionally a branch for when it is false, but {} arguments were given!",
+                            args.len()
+                        ),
+                    ));
+                }
+                if args[0].resolve()?.get().is_truthy() {
+                    Ok(args[1].new_ref())
+                } else if let Some(otherwise) = args.get(2) {
+                    Ok(otherwise.new_ref())
+                } else {
+                    Ok(Var::new(LispType::Nil))
+                }
+            }
+            // The arguments are pairs of a test and a value, made by the parser. Only the tests up to
+            // the first true one are resolved.
+            IntrinsicOp::Cond => {
+                for clause in args.chunks(2) {
+                    if clause[0].resolve()?.get().is_truthy() {
+                        return Ok(clause[1].new_ref());
+                    }
+                }
+                Ok(Var::new(LispType::Nil))
+            }
+            // Runs each argument in order, and gives back the last one.
+            IntrinsicOp::Do => match args.split_last() {
+                Some((last, rest)) => {
+                    for arg in rest {
+                        arg.resolve()?;
+                    }
+                    Ok(last.new_ref())
+                }
+                None => Ok(Var::new(LispType::Nil)),
+            },
+            IntrinsicOp::Identity => {
+                expect_args("identity", args, 1, loc_called)?;
+                Ok(args[0].new_ref())
+            }
+            _ => unreachable!("{self:?} doesn't give back one of its arguments"),
+        }
+    }
 }
 
 impl Callable for IntrinsicOp {
+    fn arity(&self) -> Arity {
+        match self {
+            IntrinsicOp::Add
+            | IntrinsicOp::Subtract
+            | IntrinsicOp::Multiply
+            | IntrinsicOp::Divide
+            | IntrinsicOp::Spread
+            | IntrinsicOp::Concat => Arity::at_least(2),
+            IntrinsicOp::Min | IntrinsicOp::Max => Arity::at_least(1),
+            IntrinsicOp::List
+            | IntrinsicOp::And
+            | IntrinsicOp::Or
+            | IntrinsicOp::Do
+            | IntrinsicOp::Gensym
+            | IntrinsicOp::Cond => Arity::ANY,
+            IntrinsicOp::Clamp => Arity::exactly(3),
+            IntrinsicOp::WithRedefs => Arity::at_least(1),
+            IntrinsicOp::FormatNumber | IntrinsicOp::Assert => Arity {
+                min: 1,
+                max: Some(2),
+            },
+            IntrinsicOp::If => Arity {
+                min: 2,
+                max: Some(3),
+            },
+            IntrinsicOp::Print
+            | IntrinsicOp::IsInteger
+            | IntrinsicOp::IsFloat
+            | IntrinsicOp::StrLen
+            | IntrinsicOp::ByteLength
+            | IntrinsicOp::BitNot
+            | IntrinsicOp::ToList
+            | IntrinsicOp::Car
+            | IntrinsicOp::Cdr
+            | IntrinsicOp::Cadr
+            | IntrinsicOp::Caddr
+            | IntrinsicOp::Cddr
+            | IntrinsicOp::Cdar
+            | IntrinsicOp::Lazy
+            | IntrinsicOp::Force
+            | IntrinsicOp::Memoize
+            | IntrinsicOp::Not
+            | IntrinsicOp::Len
+            | IntrinsicOp::TypeOf
+            | IntrinsicOp::ToStr
+            | IntrinsicOp::ParseInt
+            | IntrinsicOp::Abs
+            | IntrinsicOp::Sqrt
+            | IntrinsicOp::Identity => Arity::exactly(1),
+            IntrinsicOp::DeepEqual
+            | IntrinsicOp::Partition
+            | IntrinsicOp::Quotient
+            | IntrinsicOp::Remainder
+            | IntrinsicOp::Modulo(_)
+            | IntrinsicOp::BitAnd
+            | IntrinsicOp::BitOr
+            | IntrinsicOp::BitXor
+            | IntrinsicOp::ShiftLeft
+            | IntrinsicOp::ShiftRight
+            | IntrinsicOp::Interleave
+            | IntrinsicOp::SplitAt
+            | IntrinsicOp::WrapText
+            | IntrinsicOp::Equal
+            | IntrinsicOp::Less
+            | IntrinsicOp::Greater
+            | IntrinsicOp::LessEqual
+            | IntrinsicOp::GreaterEqual
+            | IntrinsicOp::StartsWith
+            | IntrinsicOp::EndsWith
+            | IntrinsicOp::Nth
+            | IntrinsicOp::CharAt
+            | IntrinsicOp::Map
+            | IntrinsicOp::Filter
+            | IntrinsicOp::Cons
+            | IntrinsicOp::Set => Arity::exactly(2),
+        }
+    }
+
+    fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            IntrinsicOp::Add
+                | IntrinsicOp::Subtract
+                | IntrinsicOp::Multiply
+                | IntrinsicOp::Divide
+                | IntrinsicOp::Quotient
+                | IntrinsicOp::Remainder
+                | IntrinsicOp::Modulo(_)
+                | IntrinsicOp::BitAnd
+                | IntrinsicOp::BitOr
+                | IntrinsicOp::BitXor
+                | IntrinsicOp::BitNot
+                | IntrinsicOp::ShiftLeft
+                | IntrinsicOp::ShiftRight
+                | IntrinsicOp::Clamp
+                | IntrinsicOp::Equal
+                | IntrinsicOp::Less
+                | IntrinsicOp::Greater
+                | IntrinsicOp::LessEqual
+                | IntrinsicOp::GreaterEqual
+                | IntrinsicOp::Concat
+                | IntrinsicOp::Len
+                | IntrinsicOp::Nth
+                | IntrinsicOp::CharAt
+                | IntrinsicOp::Min
+                | IntrinsicOp::Abs
+                | IntrinsicOp::Sqrt
+                | IntrinsicOp::Max
+                | IntrinsicOp::TypeOf
+                | IntrinsicOp::ToStr
+                | IntrinsicOp::ParseInt
+        )
+    }
+
+    fn is_foldable(&self) -> bool {
+        matches!(
+            self,
+            IntrinsicOp::Add
+                | IntrinsicOp::Subtract
+                | IntrinsicOp::Multiply
+                | IntrinsicOp::Divide
+                | IntrinsicOp::Quotient
+                | IntrinsicOp::Remainder
+                | IntrinsicOp::Modulo(_)
+                | IntrinsicOp::BitAnd
+                | IntrinsicOp::BitOr
+                | IntrinsicOp::BitXor
+                | IntrinsicOp::BitNot
+                | IntrinsicOp::ShiftLeft
+                | IntrinsicOp::ShiftRight
+                | IntrinsicOp::Clamp
+                | IntrinsicOp::Min
+                | IntrinsicOp::Max
+                | IntrinsicOp::Abs
+                | IntrinsicOp::Sqrt
+                | IntrinsicOp::Equal
+                | IntrinsicOp::Less
+                | IntrinsicOp::Greater
+                | IntrinsicOp::LessEqual
+                | IntrinsicOp::GreaterEqual
+        )
+    }
+
+    fn tail_arg(&self, args: &[Var], loc_called: &Location) -> Result<Option<Var>, LispErrors> {
+        match self {
+            IntrinsicOp::If | IntrinsicOp::Cond | IntrinsicOp::Do | IntrinsicOp::Identity => {
+                self.pick_tail(args, loc_called).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
         match self {
             IntrinsicOp::Add => {
-                if args.len() < 2 {
-                    println!("{} - Addition requires at least two arguments!", loc_called);
+                expect_at_least("+", args, 2, loc_called)?;
+                match numeric_args(args, "addition", loc_called)? {
+                    Numbers::Integers(nums) => {
+                        let sum = checked_fold(&nums, isize::checked_add, "+", loc_called)?;
+                        Ok(Var::new(sum.unwrap_or(0)))
+                    }
+                    Numbers::Floats(nums) => Ok(Var::new(nums.into_iter().sum::<f64>())),
                 }
-                // TODO(#11): Addition of floats and integers.
-                let mut sum = 0;
-                for a in args {
-                    if let LispType::Integer(i) = *a.resolve()?.get() {
-                        sum += i;
+            }
+            IntrinsicOp::Multiply => {
+                expect_at_least("*", args, 2, loc_called)?;
+                let product = match numeric_args(args, "multiplication", loc_called)? {
+                    Numbers::Integers(nums) => {
+                        checked_fold(&nums, isize::checked_mul, "*", loc_called)?.map(Var::new)
+                    }
+                    Numbers::Floats(nums) => nums.into_iter().reduce(|a, b| a * b).map(Var::new),
+                };
+                product.ok_or_else(|| {
+                    LispErrors::new().error(loc_called, "Cannot multiply without any arguments!")
+                })
+            }
+            IntrinsicOp::Subtract => {
+                expect_at_least("-", args, 2, loc_called)?;
+                let difference = match numeric_args(args, "subtraction", loc_called)? {
+                    Numbers::Integers(nums) => {
+                        checked_fold(&nums, isize::checked_sub, "-", loc_called)?.map(Var::new)
+                    }
+                    Numbers::Floats(nums) => nums.into_iter().reduce(|a, b| a - b).map(Var::new),
+                };
+                difference.ok_or_else(|| {
+                    LispErrors::new().error(loc_called, "Cannot subtract without any arguments!")
+                })
+            }
+            IntrinsicOp::Divide => {
+                expect_at_least("/", args, 2, loc_called)?;
+                match numeric_args(args, "division", loc_called)? {
+                    Numbers::Integers(nums) => {
+                        let mut quotient = nums[0];
+                        for &divisor in &nums[1..] {
+                            if divisor == 0 {
+                                return Err(
+                                    LispErrors::new().error(loc_called, "Division by zero!")
+                                );
+                            }
+                            quotient = match quotient.checked_div(divisor) {
+                                Some(q) => q,
+                                None => {
+                                    return Err(LispErrors::new().error(
+                                        loc_called,
+                                        format!("Overflow when dividing {quotient} by {divisor}!"),
+                                    ))
+                                }
+                            };
+                        }
+                        Ok(Var::new(quotient))
+                    }
+                    Numbers::Floats(nums) => {
+                        if nums[1..].contains(&0.0) {
+                            return Err(LispErrors::new().error(loc_called, "Division by zero!"));
+                        }
+                        Ok(Var::new(nums[1..].iter().fold(nums[0], |q, d| q / d)))
+                    }
+                }
+            }
+            // Integers stay integers, and floats stay floats.
+            IntrinsicOp::Abs => {
+                expect_args("abs", args, 1, loc_called)?;
+                match numeric_args(args, "abs", loc_called)? {
+                    Numbers::Integers(nums) => match nums[0].checked_abs() {
+                        Some(n) => Ok(Var::new(n)),
+                        None => Err(LispErrors::new()
+                            .error(loc_called, format!("Overflow in `abs` of {}!", nums[0]))),
+                    },
+                    Numbers::Floats(nums) => Ok(Var::new(nums[0].abs())),
+                }
+            }
+            IntrinsicOp::Sqrt => {
+                expect_args("sqrt", args, 1, loc_called)?;
+                let n = match numeric_args(args, "sqrt", loc_called)? {
+                    Numbers::Integers(nums) => nums[0] as f64,
+                    Numbers::Floats(nums) => nums[0],
+                };
+                if n < 0.0 {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!("Cannot take the square root of a negative number ({n})!"),
+                    ));
+                }
+                Ok(Var::new(n.sqrt()))
+            }
+            IntrinsicOp::Min | IntrinsicOp::Max => {
+                let (name, is_max) = match self {
+                    IntrinsicOp::Max => ("max", true),
+                    _ => ("min", false),
+                };
+                expect_at_least(name, args, 1, loc_called)?;
+                let extreme = match numeric_args(args, name, loc_called)? {
+                    Numbers::Integers(nums) => {
+                        let it = nums.into_iter();
+                        Var::new(if is_max { it.max() } else { it.min() }.unwrap())
+                    }
+                    Numbers::Floats(nums) => {
+                        let it = nums.into_iter();
+                        let pick = if is_max { f64::max } else { f64::min };
+                        Var::new(it.reduce(pick).unwrap())
+                    }
+                };
+                Ok(extreme)
+            }
+            // `=` compares anything, not just numbers, so `(= 1 "1")` is false instead of an error.
+            IntrinsicOp::Equal => {
+                expect_args("=", args, 2, loc_called)?;
+                let lhs = args[0].resolve()?;
+                let rhs = args[1].resolve()?;
+                let eq = *lhs.get() == *rhs.get();
+                Ok(Var::new(eq))
+            }
+            // Like `=`, but numbers only have to be close together, all the way down into lists.
+            IntrinsicOp::DeepEqual => {
+                expect_args("deep-equal", args, 2, loc_called)?;
+                let lhs = args[0].resolve()?;
+                let rhs = args[1].resolve()?;
+                let eq = lhs.get().deep_eq(&rhs.get());
+                Ok(Var::new(eq))
+            }
+            IntrinsicOp::TypeOf => {
+                expect_args("type-of", args, 1, loc_called)?;
+                Ok(Var::new(args[0].resolve()?.get().type_name()))
+            }
+            // Anything as a string, written the same way `print` would write it.
+            IntrinsicOp::ToStr => {
+                expect_args("str", args, 1, loc_called)?;
+                Ok(Var::new(args[0].resolve()?.to_string()))
+            }
+            IntrinsicOp::ParseInt => {
+                expect_args("parse-int", args, 1, loc_called)?;
+                let s = expect_str("parse-int", &args[0], loc_called)?;
+                match s.parse::<isize>() {
+                    Ok(i) => Ok(Var::new(i)),
+                    Err(e) => Err(LispErrors::new()
+                        .error(loc_called, format!("Cannot read {s:?} as an integer: {e}!"))),
+                }
+            }
+            // A symbol that no other call has given out, for naming things in generated code. Any
+            // arguments are ignored.
+            IntrinsicOp::Gensym => {
+                static NEXT_GENSYM: AtomicUsize = AtomicUsize::new(0);
+                let n = NEXT_GENSYM.fetch_add(1, AtomicOrdering::Relaxed);
+                Ok(Var::new(LispType::Symbol(format!("g{n}"))))
+            }
+            IntrinsicOp::IsInteger => {
+                expect_args("integer?", args, 1, loc_called)?;
+                let is_integer = matches!(*args[0].resolve()?.get(), LispType::Integer(_));
+                Ok(Var::new(is_integer))
+            }
+            IntrinsicOp::IsFloat => {
+                expect_args("float?", args, 1, loc_called)?;
+                let is_float = matches!(*args[0].resolve()?.get(), LispType::Floating(_));
+                Ok(Var::new(is_float))
+            }
+            IntrinsicOp::StrLen => {
+                expect_args("strlen", args, 1, loc_called)?;
+                match &*args[0].resolve()?.get() {
+                    LispType::Str(s) => Ok(Var::new(s.chars().count() as isize)),
+                    other => Err(LispErrors::new().error(
+                        loc_called,
+                        format!("`strlen` requires a string, not {}!", other.type_name()),
+                    )),
+                }
+            }
+            IntrinsicOp::ByteLength => {
+                expect_args("byte-length", args, 1, loc_called)?;
+                match &*args[0].resolve()?.get() {
+                    LispType::Str(s) => Ok(Var::new(s.len() as isize)),
+                    other => Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "`byte-length` requires a string, not {}!",
+                            other.type_name()
+                        ),
+                    )),
+                }
+            }
+            IntrinsicOp::Partition => {
+                expect_args("partition", args, 2, loc_called)?;
+                let pred = expect_func("partition", &args[0], loc_called)?;
+                let items = expect_list("partition", &args[1], loc_called)?;
+                let (mut matching, mut rest) = (Vec::new(), Vec::new());
+                for item in items {
+                    let item = item.resolve()?;
+                    let keep = pred
+                        .callable(loc_called)?
+                        .call(&[item.new_ref()], loc_called)?
+                        .resolve()?
+                        .get()
+                        .is_truthy();
+                    if keep {
+                        matching.push(item);
                     } else {
-                        return Err(LispErrors::new().error(
-                            loc_called,
-                            format!("Incompatible types for addition: Integer and {}", a.get()),
-                        ));
+                        rest.push(item);
                     }
                 }
-                Ok(Var::new(sum))
+                Ok(Var::new(LispType::List(vec![
+                    Var::new(LispType::List(matching)),
+                    Var::new(LispType::List(rest)),
+                ])))
             }
-            IntrinsicOp::Multiply => {
-                if args.len() < 2 {
-                    println!(
-                        "{} - Multiplication requires at least two arguments!",
-                        loc_called
-                    );
-                }
-                let mut product;
-                let t = args.get(0).unwrap();
-                if let LispType::Integer(i) = *t.resolve()?.get() {
-                    product = i
+            // These follow Scheme: `quotient` truncates towards zero, the sign of `remainder`
+            // follows the dividend, and the sign of `modulo` follows the divisor.
+            IntrinsicOp::Quotient => {
+                let (dividend, divisor) = division_args("quotient", args, loc_called)?;
+                match dividend.checked_div(divisor) {
+                    Some(q) => Ok(Var::new(q)),
+                    None => Err(LispErrors::new().error(
+                        loc_called,
+                        format!("Overflow in `quotient` of {dividend} and {divisor}!"),
+                    )),
+                }
+            }
+            IntrinsicOp::Remainder => {
+                let (dividend, divisor) = division_args("remainder", args, loc_called)?;
+                // `checked_rem` only fails for `isize::MIN % -1`, which is 0 anyway.
+                Ok(Var::new(dividend.checked_rem(divisor).unwrap_or(0)))
+            }
+            IntrinsicOp::Modulo(name) => {
+                let (dividend, divisor) = division_args(name, args, loc_called)?;
+                let rem = dividend.checked_rem(divisor).unwrap_or(0);
+                if rem != 0 && (rem < 0) != (divisor < 0) {
+                    Ok(Var::new(rem + divisor))
                 } else {
-                    return Err(LispErrors::new()
-                        .error(loc_called, "Cannot multiply with non-integer type!"));
+                    Ok(Var::new(rem))
                 }
-                for a in args.iter().skip(1) {
-                    if let LispType::Integer(i) = *a.resolve()?.get() {
-                        product *= i;
+            }
+            IntrinsicOp::BitAnd | IntrinsicOp::BitOr | IntrinsicOp::BitXor => {
+                let name = match self {
+                    IntrinsicOp::BitAnd => "bit-and",
+                    IntrinsicOp::BitOr => "bit-or",
+                    _ => "bit-xor",
+                };
+                expect_args(name, args, 2, loc_called)?;
+                let lhs = expect_int(name, &args[0], loc_called)?;
+                let rhs = expect_int(name, &args[1], loc_called)?;
+                Ok(Var::new(match self {
+                    IntrinsicOp::BitAnd => lhs & rhs,
+                    IntrinsicOp::BitOr => lhs | rhs,
+                    _ => lhs ^ rhs,
+                }))
+            }
+            IntrinsicOp::BitNot => {
+                expect_args("bit-not", args, 1, loc_called)?;
+                Ok(Var::new(!expect_int("bit-not", &args[0], loc_called)?))
+            }
+            IntrinsicOp::ShiftLeft | IntrinsicOp::ShiftRight => {
+                let name = if let IntrinsicOp::ShiftLeft = self {
+                    "shl"
+                } else {
+                    "shr"
+                };
+                expect_args(name, args, 2, loc_called)?;
+                let value = expect_int(name, &args[0], loc_called)?;
+                let count = expect_int(name, &args[1], loc_called)?;
+                if count < 0 {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!("`{name}` cannot shift by a negative amount ({count})!"),
+                    ));
+                }
+                let shifted = u32::try_from(count).ok().and_then(|c| {
+                    if let IntrinsicOp::ShiftLeft = self {
+                        value.checked_shl(c)
                     } else {
-                        return Err(LispErrors::new()
-                            .error(loc_called, "Cannot multiply with non-integer type!"));
+                        value.checked_shr(c)
                     }
+                });
+                match shifted {
+                    Some(s) => Ok(Var::new(s)),
+                    None => Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "`{name}` cannot shift by {count}, which is more than an integer's {} bits!",
+                            isize::BITS
+                        ),
+                    )),
                 }
-                Ok(Var::new(product))
             }
-            IntrinsicOp::Subtract => {
-                if args.len() < 2 {
-                    println!(
-                        "{} - Subtraction requires at least two arguments!",
-                        loc_called
-                    );
-                }
-                let mut sum;
-                let t = args.get(0).unwrap();
-                if let LispType::Integer(i) = *t.resolve()?.get() {
-                    sum = i
+            IntrinsicOp::Interleave => {
+                expect_args("interleave", args, 2, loc_called)?;
+                let lhs = expect_list("interleave", &args[0], loc_called)?;
+                let rhs = expect_list("interleave", &args[1], loc_called)?;
+                let items = lhs.into_iter().zip(rhs).flat_map(|(l, r)| [l, r]).collect();
+                Ok(Var::new(LispType::List(items)))
+            }
+            // TODO: Maps should become lists of `(key value)` pairs once they exist.
+            IntrinsicOp::ToList => {
+                expect_args("to-list", args, 1, loc_called)?;
+                let value = args[0].resolve()?;
+                let chars = match &*value.get() {
+                    LispType::List(_) => None,
+                    LispType::Str(s) => Some(s.chars().map(|c| Var::new(c.to_string())).collect()),
+                    other => {
+                        return Err(LispErrors::new().error(
+                            loc_called,
+                            format!("Cannot convert {} to a list!", other.type_name()),
+                        ))
+                    }
+                };
+                match chars {
+                    Some(chars) => Ok(Var::new(LispType::List(chars))),
+                    None => Ok(value),
+                }
+            }
+            IntrinsicOp::SplitAt => {
+                expect_args("split-at", args, 2, loc_called)?;
+                let mut prefix = expect_list("split-at", &args[0], loc_called)?;
+                let idx = expect_int("split-at", &args[1], loc_called)?;
+                if idx < 0 {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!("Cannot split a list at a negative index ({idx})!"),
+                    ));
+                }
+                let suffix = prefix.split_off((idx as usize).min(prefix.len()));
+                Ok(Var::new(LispType::List(vec![
+                    Var::new(LispType::List(prefix)),
+                    Var::new(LispType::List(suffix)),
+                ])))
+            }
+            IntrinsicOp::WrapText => {
+                expect_args("wrap-text", args, 2, loc_called)?;
+                let text = args[0].resolve()?;
+                let width = expect_int("wrap-text", &args[1], loc_called)?;
+                if width <= 0 {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!("Cannot wrap text to a width of {width}!"),
+                    ));
+                }
+                let wrapped = match &*text.get() {
+                    LispType::Str(s) => wrap_text(s, width as usize),
+                    other => {
+                        return Err(LispErrors::new().error(
+                            loc_called,
+                            format!("`wrap-text` requires a string, not {}!", other.type_name()),
+                        ))
+                    }
+                };
+                Ok(Var::new(wrapped))
+            }
+            IntrinsicOp::Car => {
+                expect_args("car", args, 1, loc_called)?;
+                car("car", &args[0], loc_called)
+            }
+            IntrinsicOp::Cdr => {
+                expect_args("cdr", args, 1, loc_called)?;
+                cdr("cdr", &args[0], loc_called)
+            }
+            // A new list with the item in front, which shares the rest of its items with the old one.
+            IntrinsicOp::Cons => {
+                expect_args("cons", args, 2, loc_called)?;
+                let first = args[0].resolve()?;
+                let rest = expect_list("cons", &args[1], loc_called)?;
+                Ok(Var::new(LispType::List(
+                    std::iter::once(first).chain(rest).collect(),
+                )))
+            }
+            IntrinsicOp::Cadr => {
+                expect_args("cadr", args, 1, loc_called)?;
+                car("cadr", &cdr("cadr", &args[0], loc_called)?, loc_called)
+            }
+            IntrinsicOp::Caddr => {
+                expect_args("caddr", args, 1, loc_called)?;
+                let rest = cdr("caddr", &cdr("caddr", &args[0], loc_called)?, loc_called)?;
+                car("caddr", &rest, loc_called)
+            }
+            IntrinsicOp::Cddr => {
+                expect_args("cddr", args, 1, loc_called)?;
+                cdr("cddr", &cdr("cddr", &args[0], loc_called)?, loc_called)
+            }
+            IntrinsicOp::Cdar => {
+                expect_args("cdar", args, 1, loc_called)?;
+                cdr("cdar", &car("cdar", &args[0], loc_called)?, loc_called)
+            }
+            IntrinsicOp::Clamp => {
+                expect_args("clamp", args, 3, loc_called)?;
+                // Resolved up front, so that the one that is returned isn't resolved twice.
+                let args = args
+                    .iter()
+                    .map(Var::resolve)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let nums = numeric_args(&args, "clamp", loc_called)?.into_floats();
+                let (x, lo, hi) = (nums[0], nums[1], nums[2]);
+                if lo > hi {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "The lower bound of `clamp` ({}) is greater than the upper bound ({})!",
+                            args[1], args[2]
+                        ),
+                    ));
+                }
+                if x < lo {
+                    Ok(args[1].new_ref())
+                } else if x > hi {
+                    Ok(args[2].new_ref())
                 } else {
-                    return Err(
-                        LispErrors::new().error(loc_called, "Cannot subtract from a non-integer!")
-                    );
+                    Ok(args[0].new_ref())
                 }
-                for a in args.iter().skip(1) {
-                    if let LispType::Integer(i) = *a.resolve()?.get() {
-                        sum -= i;
-                    } else {
+            }
+            IntrinsicOp::Less
+            | IntrinsicOp::Greater
+            | IntrinsicOp::LessEqual
+            | IntrinsicOp::GreaterEqual => {
+                let name = match self {
+                    IntrinsicOp::Less => "<",
+                    IntrinsicOp::Greater => ">",
+                    IntrinsicOp::LessEqual => "<=",
+                    _ => ">=",
+                };
+                expect_args(name, args, 2, loc_called)?;
+                // Unlike `=`, these compare floats exactly, so close but different floats are still
+                // ordered. Nothing is ordered with NaN.
+                let ordering = match numeric_args(args, "comparison", loc_called)? {
+                    Numbers::Integers(nums) => Some(nums[0].cmp(&nums[1])),
+                    Numbers::Floats(nums) => nums[0].partial_cmp(&nums[1]),
+                };
+                let is = |pick: fn(Ordering) -> bool| ordering.is_some_and(pick);
+                Ok(Var::new(match self {
+                    IntrinsicOp::Less => is(Ordering::is_lt),
+                    IntrinsicOp::Greater => is(Ordering::is_gt),
+                    IntrinsicOp::LessEqual => is(Ordering::is_le),
+                    _ => is(Ordering::is_ge),
+                }))
+            }
+            // `(spread f a b [c d])` is the same as `(f a b c d)`.
+            IntrinsicOp::Spread => {
+                if args.len() < 2 {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        "`spread` requires a function and a list of arguments!",
+                    ));
+                }
+                let f = expect_func("spread", &args[0], loc_called)?;
+                let (last, middle) = args[1..].split_last().unwrap();
+                let mut spread_args: Vec<Var> = middle.iter().map(Var::new_ref).collect();
+                spread_args.extend(expect_list("spread", last, loc_called)?);
+                f.callable(loc_called)?.call(&spread_args, loc_called)
+            }
+            // `(map f list)` calls `f` on each item, and is a list of the results.
+            IntrinsicOp::Map => {
+                expect_args("map", args, 2, loc_called)?;
+                let f = expect_func("map", &args[0], loc_called)?.callable(loc_called)?;
+                let items = expect_list("map", &args[1], loc_called)?;
+                let mut mapped = Vec::with_capacity(items.len());
+                for (i, item) in items.into_iter().enumerate() {
+                    let res = f
+                        .call(&[item], loc_called)
+                        .and_then(|r| r.resolve())
+                        .map_err(|e| {
+                            e.note(None, format!("While mapping item {i} of the list."))
+                        })?;
+                    mapped.push(res);
+                }
+                Ok(Var::new(LispType::List(mapped)))
+            }
+            // `(filter pred list)` is a list of the items that `pred` is true for.
+            IntrinsicOp::Filter => {
+                expect_args("filter", args, 2, loc_called)?;
+                let pred = expect_func("filter", &args[0], loc_called)?.callable(loc_called)?;
+                let items = expect_list("filter", &args[1], loc_called)?;
+                let mut kept = Vec::new();
+                for (i, item) in items.into_iter().enumerate() {
+                    let keep = pred
+                        .call(&[item.new_ref()], loc_called)
+                        .and_then(|r| r.resolve())
+                        .map_err(|e| {
+                            e.note(None, format!("While filtering item {i} of the list."))
+                        })?;
+                    if keep.get().is_truthy() {
+                        kept.push(item);
+                    }
+                }
+                Ok(Var::new(LispType::List(kept)))
+            }
+            // `(format-number 1234567)` is `"1,234,567"`, and `(format-number 1234567 " ")` is
+            // `"1 234 567"`.
+            IntrinsicOp::FormatNumber => {
+                if !self.arity().allows(args.len()) {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "`format-number` requires a number and optionally a separator, but {} arguments were given!",
+                            args.len()
+                        ),
+                    ));
+                }
+                let number = match &*args[0].resolve()?.get() {
+                    LispType::Integer(i) => i.to_string(),
+                    LispType::Floating(f) => f.to_string(),
+                    other => {
                         return Err(LispErrors::new().error(
                             loc_called,
-                            "Cannot subtract a non-integer type from an integer!",
-                        ));
+                            format!(
+                                "`format-number` requires a number, not {}!",
+                                other.type_name()
+                            ),
+                        ))
+                    }
+                };
+                let separator = match args.get(1) {
+                    Some(arg) => match &*arg.resolve()?.get() {
+                        LispType::Str(s) => s.clone(),
+                        other => {
+                            return Err(LispErrors::new().error(
+                                loc_called,
+                                format!(
+                                    "The separator for `format-number` must be a string, not {}!",
+                                    other.type_name()
+                                ),
+                            ))
+                        }
+                    },
+                    None => ",".to_string(),
+                };
+                Ok(Var::new(group_digits(&number, &separator)))
+            }
+            // The argument isn't resolved here, so it only runs when the promise is forced.
+            IntrinsicOp::Lazy => {
+                expect_args("lazy", args, 1, loc_called)?;
+                Ok(Var::new(Promise::new(args[0].new_ref())))
+            }
+            // Forcing something that isn't a promise just gives it back.
+            IntrinsicOp::Force => {
+                expect_args("force", args, 1, loc_called)?;
+                let value = args[0].resolve()?;
+                let forced = match &*value.get() {
+                    LispType::Promise(p) => p.force()?,
+                    _ => value.new_ref(),
+                };
+                Ok(forced)
+            }
+            IntrinsicOp::StartsWith => {
+                expect_args("starts-with?", args, 2, loc_called)?;
+                let s = expect_str("starts-with?", &args[0], loc_called)?;
+                let prefix = expect_str("starts-with?", &args[1], loc_called)?;
+                Ok(Var::new(s.starts_with(&prefix)))
+            }
+            IntrinsicOp::EndsWith => {
+                expect_args("ends-with?", args, 2, loc_called)?;
+                let s = expect_str("ends-with?", &args[0], loc_called)?;
+                let suffix = expect_str("ends-with?", &args[1], loc_called)?;
+                Ok(Var::new(s.ends_with(&suffix)))
+            }
+            // `(assert condition)` or `(assert condition "message")`, which errors if the condition
+            // is false.
+            IntrinsicOp::Assert => {
+                if !self.arity().allows(args.len()) {
+                    return Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "`assert` requires a condition and optionally a message, but {} arguments were given!",
+                            args.len()
+                        ),
+                    ));
+                }
+                if args[0].resolve()?.get().is_truthy() {
+                    return Ok(Var::new(LispType::Nil));
+                }
+                match args.get(1) {
+                    Some(msg) => Err(LispErrors::new()
+                        .error(loc_called, format!("Assertion failed: {}", msg.resolve()?))),
+                    None => Err(LispErrors::new().error(loc_called, "Assertion failed!")),
+                }
+            }
+            IntrinsicOp::Concat => {
+                expect_at_least("concat", args, 2, loc_called)?;
+                let mut joined = String::new();
+                for a in args {
+                    joined.push_str(&expect_str("concat", a, loc_called)?);
+                }
+                Ok(Var::new(joined))
+            }
+            // The number of characters in a string or items in a list.
+            IntrinsicOp::Len => {
+                expect_args("len", args, 1, loc_called)?;
+                match &*args[0].resolve()?.get() {
+                    LispType::Str(s) => Ok(Var::new(s.chars().count() as isize)),
+                    LispType::List(items) => Ok(Var::new(items.len() as isize)),
+                    other => Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "`len` requires a string or a list, not {}!",
+                            other.type_name()
+                        ),
+                    )),
+                }
+            }
+            // `(nth index list)`, counting from 0.
+            IntrinsicOp::Nth => {
+                expect_args("nth", args, 2, loc_called)?;
+                let index = expect_int("nth", &args[0], loc_called)?;
+                let items = expect_list("nth", &args[1], loc_called)?;
+                match usize::try_from(index).ok().and_then(|i| items.get(i)) {
+                    Some(item) => Ok(item.new_ref()),
+                    None => Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "Index {index} is out of range for a list of length {}!",
+                            items.len()
+                        ),
+                    )),
+                }
+            }
+            // `(char-at string index)`, counting characters from 0.
+            IntrinsicOp::CharAt => {
+                expect_args("char-at", args, 2, loc_called)?;
+                let s = expect_str("char-at", &args[0], loc_called)?;
+                let index = expect_int("char-at", &args[1], loc_called)?;
+                match usize::try_from(index).ok().and_then(|i| s.chars().nth(i)) {
+                    Some(c) => Ok(Var::new(c)),
+                    None => Err(LispErrors::new().error(
+                        loc_called,
+                        format!(
+                            "Index {index} is out of range for a string of length {}!",
+                            s.chars().count()
+                        ),
+                    )),
+                }
+            }
+            IntrinsicOp::List => {
+                let items = args
+                    .iter()
+                    .map(Var::resolve)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Var::new(LispType::List(items)))
+            }
+            IntrinsicOp::Memoize => {
+                expect_args("memoize", args, 1, loc_called)?;
+                let f = expect_func("memoize", &args[0], loc_called)?;
+                Ok(Var::new(Memoized::new(f)))
+            }
+            IntrinsicOp::Not => {
+                expect_args("not", args, 1, loc_called)?;
+                Ok(Var::new(!args[0].resolve()?.get().is_truthy()))
+            }
+            // `and` gives back the first argument that is false and `or` the first one that is true,
+            // without resolving the rest. If there isn't one, it is the last argument.
+            IntrinsicOp::And | IntrinsicOp::Or => {
+                let stop_when = matches!(self, IntrinsicOp::Or);
+                let mut res = Var::new(!stop_when);
+                for arg in args {
+                    res = arg.resolve()?;
+                    if res.get().is_truthy() == stop_when {
+                        break;
                     }
                 }
-                Ok(Var::new(sum))
+                Ok(res)
+            }
+            // These give back one of their arguments, which `pick_tail` picks.
+            IntrinsicOp::If | IntrinsicOp::Cond | IntrinsicOp::Do | IntrinsicOp::Identity => {
+                self.pick_tail(args, loc_called)?.resolve()
+            }
+            // Every variable gets its old value back afterwards, even if something failed.
+            IntrinsicOp::WithRedefs => {
+                let (body, pairs) = args.split_last().unwrap();
+                let mut saved = Vec::with_capacity(pairs.len() / 2);
+                let mut run = || {
+                    for pair in pairs.chunks(2) {
+                        let value = pair[1].resolve()?;
+                        let value = value.get().clone();
+                        let old = mem::replace(&mut *pair[0].get_mut(), value);
+                        saved.push((pair[0].new_ref(), old));
+                    }
+                    // A copy, since the result could be one of the variables that is changed back.
+                    let res = body.resolve()?;
+                    let res = res.get().clone();
+                    Ok(Var::new(res))
+                };
+                let res = run();
+                for (var, old) in saved.into_iter().rev() {
+                    *var.get_mut() = old;
+                }
+                res
+            }
+            IntrinsicOp::Set => {
+                expect_args("set", args, 2, loc_called)?;
+                let value = args[1].resolve()?;
+                let new_value = value.get().clone();
+                *args[0].get_mut() = new_value;
+                Ok(args[0].new_ref())
             }
             IntrinsicOp::Print => {
                 if args.len() != 1 {
                     Err(LispErrors::new()
-                        .error(loc_called, "Print intrinsic requires only one argument!")
+                        .error(
+                            loc_called,
+                            format!(
+                                "Print intrinsic requires only one argument, but {} were given!",
+                                args.len()
+                            ),
+                        )
                         .note(None, "Try wrapping this in a statement with `$`."))
                 } else {
-                    println!("{}", args[0]);
-                    Ok(Var::new(0))
+                    // Gives back what it printed, so that it can be put around any expression.
+                    let value = args[0].resolve()?;
+                    println!("{value}");
+                    Ok(value)
+                }
+            }
+        }
+    }
+}
+
+// Picks an implementation based on the type of the first (resolved) argument.
+#[derive(Debug, Default, Clone)]
+pub struct MultiMethod {
+    impls: BTreeMap<&'static str, Box<dyn Callable>>,
+}
+
+impl MultiMethod {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // `type_name` is the name given by `LispType::type_name`, e.g. "integer" or "string".
+    pub fn register<T: Callable + 'static>(&mut self, type_name: &'static str, f: T) {
+        self.impls.insert(type_name, Box::new(f));
+    }
+}
+
+impl Callable for MultiMethod {
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        let first = match args.first() {
+            Some(a) => a.resolve()?,
+            None => {
+                return Err(LispErrors::new()
+                    .error(loc_called, "Cannot dispatch a call with no arguments!"))
+            }
+        };
+        let type_name = first.get().type_name();
+        match self.impls.get(type_name) {
+            Some(f) => {
+                // The first argument is already resolved, so pass that along instead of resolving it twice.
+                let mut resolved = vec![first];
+                resolved.extend(args.iter().skip(1).map(Var::new_ref));
+                f.call(&resolved, loc_called)
+            }
+            None => Err(LispErrors::new()
+                .error(
+                    loc_called,
+                    format!("No implementation for arguments of type `{type_name}`!"),
+                )
+                .note(
+                    None,
+                    format!(
+                        "Implementations exist for: {}",
+                        self.impls.keys().copied().collect::<Vec<_>>().join(", ")
+                    ),
+                )),
+        }
+    }
+}
+
+// Gets the current Unix time in seconds. The clock can be swapped out, so that hosts can make it
+// deterministic.
+#[derive(Debug, Clone)]
+pub struct TimeNow {
+    clock: fn() -> SystemTime,
+}
+
+impl TimeNow {
+    pub fn with_clock(clock: fn() -> SystemTime) -> Self {
+        Self { clock }
+    }
+}
+
+impl Default for TimeNow {
+    fn default() -> Self {
+        Self::with_clock(SystemTime::now)
+    }
+}
+
+impl Callable for TimeNow {
+    fn arity(&self) -> Arity {
+        Arity::exactly(0)
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        expect_args("time-now", args, 0, loc_called)?;
+        match (self.clock)().duration_since(UNIX_EPOCH) {
+            Ok(d) => Ok(Var::new(d.as_secs() as isize)),
+            Err(_) => Err(LispErrors::new().error(loc_called, "The clock is set before 1970!")),
+        }
+    }
+}
+
+// Reads a line of input, without the line ending, or gives `nil` at the end of the input. The input
+// can be swapped out, so that hosts can feed it whatever they want.
+// Copies share the same input.
+#[derive(Clone)]
+pub struct ReadLine {
+    reader: Rc<RefCell<Box<dyn BufRead>>>,
+}
+
+impl ReadLine {
+    pub fn with_reader<R: BufRead + 'static>(reader: R) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(Box::new(reader))),
+        }
+    }
+}
+
+impl Default for ReadLine {
+    fn default() -> Self {
+        Self::with_reader(io::BufReader::new(io::stdin()))
+    }
+}
+
+impl Debug for ReadLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadLine").finish_non_exhaustive()
+    }
+}
+
+impl Callable for ReadLine {
+    fn arity(&self) -> Arity {
+        Arity::exactly(0)
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        expect_args("read-line", args, 0, loc_called)?;
+        let mut line = String::new();
+        match self.reader.borrow_mut().read_line(&mut line) {
+            Ok(0) => Ok(Var::new(LispType::Nil)),
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Ok(Var::new(line))
+            }
+            Err(e) => {
+                Err(LispErrors::new().error(loc_called, format!("Could not read a line: {e}")))
+            }
+        }
+    }
+}
+
+// Prints everything that was in scope where it was written, to stderr unless it is given something
+// else to write to.
+#[derive(Clone)]
+pub struct DebugScope {
+    scope: Scope,
+    out: Rc<RefCell<Box<dyn Write>>>,
+}
+
+impl DebugScope {
+    pub(crate) fn new(scope: Scope) -> Self {
+        Self::with_writer(scope, io::stderr())
+    }
+    pub(crate) fn with_writer<W: Write + 'static>(scope: Scope, out: W) -> Self {
+        Self {
+            scope,
+            out: Rc::new(RefCell::new(Box::new(out))),
+        }
+    }
+}
+
+impl Debug for DebugScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugScope")
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Callable for DebugScope {
+    fn arity(&self) -> Arity {
+        Arity::exactly(0)
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        expect_args("debug-scope", args, 0, loc_called)?;
+        write!(
+            self.out.borrow_mut(),
+            "{loc_called} - Scope:\n{}",
+            self.scope.dump()
+        )
+        .map_err(|e| {
+            LispErrors::new().error(loc_called, format!("Could not print the scope: {e}"))
+        })?;
+        Ok(Var::new(LispType::Nil))
+    }
+}
+
+// A function defined in the source with `fn`. Names are bound to values when they are parsed, so
+// the body is parsed again for every call, with the parameters bound to the arguments.
+#[derive(Clone)]
+pub struct LispFn {
+    params: Vec<String>,
+    body: Vec<Token>,
+    // Where the function was defined, which each call's parameters are bound inside of.
+    scope: Rc<Scope>,
+}
+
+impl LispFn {
+    pub(crate) fn new(params: Vec<String>, body: Vec<Token>, scope: Rc<Scope>) -> Self {
+        Self {
+            params,
+            body,
+            scope,
+        }
+    }
+}
+
+// The scope can contain the function itself, so it isn't printed.
+impl Debug for LispFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LispFn")
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Callable for LispFn {
+    fn arity(&self) -> Arity {
+        Arity::exactly(self.params.len())
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        if !self.arity().allows(args.len()) {
+            return Err(LispErrors::new().error(
+                loc_called,
+                format!(
+                    "This function takes {}, but {} were given!",
+                    self.arity(),
+                    args.len()
+                ),
+            ));
+        }
+        let mut args = args
+            .iter()
+            .map(Var::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+        // Each time around is one call. A call to this function as the last thing the body does
+        // starts the next one instead of recursing, so that it doesn't use up the stack.
+        'call: loop {
+            let mut scope = Scope::child(Rc::clone(&self.scope));
+            for (param, arg) in self.params.iter().zip(args) {
+                scope.vars.insert(param.clone(), arg);
+            }
+            let mut expr = Var::new(make_ast(&self.body, &mut scope, &self.body[0].loc)?);
+            loop {
+                let stmt = match &*expr.get() {
+                    LispType::Statement(s) => s.clone(),
+                    _ => break,
+                };
+                let f = stmt.op.callable(&stmt.loc)?;
+                let calls_self = std::ptr::addr_eq(Rc::as_ptr(&f), self as *const Self);
+                if calls_self && self.arity().allows(stmt.args.len()) {
+                    args = stmt
+                        .args
+                        .iter()
+                        .map(Var::resolve)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    continue 'call;
                 }
+                match f.tail_arg(&stmt.args, &stmt.loc)? {
+                    Some(next) => expr = next,
+                    None => break,
+                }
+            }
+            return expr.resolve();
+        }
+    }
+}
+
+// A function made by `case-lambda`, which runs the first of its cases that takes as many arguments
+// as it was called with. Each case is a `LispFn`.
+#[derive(Debug, Clone)]
+pub struct CaseLambda {
+    cases: Vec<Var>,
+}
+
+impl CaseLambda {
+    pub(crate) fn new(cases: Vec<Var>) -> Self {
+        Self { cases }
+    }
+
+    fn case_arities(&self) -> impl Iterator<Item = Arity> + '_ {
+        self.cases.iter().map(|case| match &*case.get() {
+            LispType::Func(f) => f.arity(),
+            _ => Arity::ANY,
+        })
+    }
+}
+
+impl Callable for CaseLambda {
+    // Anything from the fewest arguments a case takes to the most, even if there is a gap between
+    // cases. Calling it with a number that falls in a gap is an error.
+    fn arity(&self) -> Arity {
+        let min = self.case_arities().map(|a| a.min).min().unwrap_or(0);
+        let max = self
+            .case_arities()
+            .map(|a| a.max)
+            .reduce(|a, b| a.zip(b).map(|(a, b)| a.max(b)));
+        Arity {
+            min,
+            max: max.flatten(),
+        }
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        let case = self
+            .cases
+            .iter()
+            .zip(self.case_arities())
+            .find(|(_, arity)| arity.allows(args.len()));
+        match case {
+            Some((case, _)) => case.callable(loc_called)?.call(args, loc_called),
+            None => Err(LispErrors::new().error(
+                loc_called,
+                format!(
+                    "No case of this `case-lambda` takes {} arguments!",
+                    args.len()
+                ),
+            )),
+        }
+    }
+}
+
+// An argument to a memoized function, compared exactly, so that `1` and `1.0` (or two floats that are
+// only close together) aren't mistaken for each other. Floats are compared by their bits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MemoKey {
+    Integer(isize),
+    Floating(u64),
+    Str(String),
+    Bool(bool),
+    Char(char),
+    Symbol(String),
+    Nil,
+    List(Vec<MemoKey>),
+}
+
+impl MemoKey {
+    // Functions, promises and lists that contain themselves can't be compared, so calls with them
+    // aren't remembered.
+    fn new(value: &Var, seen: &mut Vec<*const RefCell<LispType>>) -> Option<MemoKey> {
+        let ptr = Rc::as_ptr(&value.dat);
+        if seen.contains(&ptr) {
+            return None;
+        }
+        Some(match &*value.get() {
+            LispType::Integer(i) => MemoKey::Integer(*i),
+            LispType::Floating(f) => MemoKey::Floating(f.to_bits()),
+            LispType::Str(s) => MemoKey::Str(s.clone()),
+            LispType::Bool(b) => MemoKey::Bool(*b),
+            LispType::Char(c) => MemoKey::Char(*c),
+            LispType::Symbol(name) => MemoKey::Symbol(name.clone()),
+            LispType::Nil => MemoKey::Nil,
+            LispType::List(items) => {
+                seen.push(ptr);
+                let keys = items
+                    .iter()
+                    .map(|item| MemoKey::new(item, seen))
+                    .collect::<Option<Vec<_>>>();
+                seen.pop();
+                MemoKey::List(keys?)
+            }
+            LispType::Func(_) | LispType::Statement(_) | LispType::Promise(_) => return None,
+        })
+    }
+}
+
+// A function made by `memoize`, which remembers what it gave back for each set of arguments instead
+// of calling the inner function again. Arguments have to be exactly the same to count, so it should
+// only be used with pure functions.
+#[derive(Debug, Clone)]
+pub struct Memoized {
+    inner: Var,
+    cache: RefCell<HashMap<Vec<MemoKey>, Var>>,
+}
+
+impl Memoized {
+    pub(crate) fn new(inner: Var) -> Self {
+        Self {
+            inner,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Callable for Memoized {
+    fn arity(&self) -> Arity {
+        // If it isn't a function anymore, calling it reports that.
+        match &*self.inner.get() {
+            LispType::Func(f) => f.arity(),
+            _ => Arity::ANY,
+        }
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        let args = args
+            .iter()
+            .map(Var::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = args
+            .iter()
+            .map(|arg| MemoKey::new(arg, &mut Vec::new()))
+            .collect::<Option<Vec<_>>>();
+        if let Some(res) = key
+            .as_ref()
+            .and_then(|key| self.cache.borrow().get(key).map(Var::new_ref))
+        {
+            return Ok(res);
+        }
+        let res = self.inner.callable(loc_called)?.call(&args, loc_called)?;
+        if let Some(key) = key {
+            self.cache.borrow_mut().insert(key, res.new_ref());
+        }
+        Ok(res)
+    }
+}
+
+// The tests registered with `deftest`, by name, in the order they were defined.
+pub(crate) type TestRegistry = Rc<RefCell<Vec<(String, Var)>>>;
+
+// `(deftest "name" body)` saves the body to be run by `run-tests` instead of running it.
+#[derive(Debug, Clone)]
+pub struct DefTest {
+    registry: TestRegistry,
+}
+
+impl DefTest {
+    pub(crate) fn new(registry: TestRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Callable for DefTest {
+    fn arity(&self) -> Arity {
+        Arity::exactly(2)
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        expect_args("deftest", args, 2, loc_called)?;
+        let name = expect_str("deftest", &args[0], loc_called)?;
+        let mut registry = self.registry.borrow_mut();
+        if registry.iter().any(|(existing, _)| *existing == name) {
+            return Err(LispErrors::new().error(
+                loc_called,
+                format!("There is already a test named {name:?}!"),
+            ));
+        }
+        registry.push((name, args[1].new_ref()));
+        Ok(Var::new(LispType::Nil))
+    }
+}
+
+// Runs every registered test, printing which ones failed and a summary, and evaluates to the number
+// of tests that failed. A failing test doesn't stop the others from running. The results go to
+// stdout unless it is given something else to write to.
+#[derive(Clone)]
+pub struct RunTests {
+    registry: TestRegistry,
+    out: Rc<RefCell<Box<dyn Write>>>,
+}
+
+impl RunTests {
+    pub(crate) fn new(registry: TestRegistry) -> Self {
+        Self::with_writer(registry, io::stdout())
+    }
+    pub(crate) fn with_writer<W: Write + 'static>(registry: TestRegistry, out: W) -> Self {
+        Self {
+            registry,
+            out: Rc::new(RefCell::new(Box::new(out))),
+        }
+    }
+}
+
+impl Debug for RunTests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunTests")
+            .field("registry", &self.registry)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Callable for RunTests {
+    fn arity(&self) -> Arity {
+        Arity::exactly(0)
+    }
+
+    fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+        expect_args("run-tests", args, 0, loc_called)?;
+        // The bodies can define more tests, so the registry can't stay borrowed while they run.
+        // Those are left for the next `run-tests`.
+        let tests = self.registry.borrow().clone();
+        let print_err = |e: io::Error| {
+            LispErrors::new().error(loc_called, format!("Could not print the test results: {e}"))
+        };
+        let mut failures = 0;
+        for (name, body) in &tests {
+            if let Err(e) = body.resolve() {
+                writeln!(self.out.borrow_mut(), "FAIL {name}: {e}").map_err(print_err)?;
+                failures += 1;
             }
         }
+        writeln!(
+            self.out.borrow_mut(),
+            "{} passed, {failures} failed",
+            tests.len() - failures as usize
+        )
+        .map_err(print_err)?;
+        Ok(Var::new(failures))
     }
 }
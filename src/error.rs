@@ -2,14 +2,75 @@ use std::{error::Error, fmt::Display};
 
 use crate::tokens::Location;
 
-#[derive(Debug)]
+// TODO: Errors don't have a category yet (type error, arithmetic error, user error...). Once there is
+// a `catch` form, a `catch-type` that only intercepts some categories would need one per error.
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct LispErrors {
-    errs: Vec<(String, Vec<String>)>,
+    // The message, its notes, and what went wrong if it was a parse error.
+    errs: Vec<(String, Vec<String>, Option<ParseError>)>,
+}
+
+// The parse errors that an embedder might want to handle differently from the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    // `paren` is the unmatched `(`, `)`, `[` or `]`.
+    UnmatchedParen { loc: Location, paren: char },
+    UnknownIdent { name: String, loc: Location },
+    EmptyStatement { loc: Location },
+}
+
+impl ParseError {
+    pub fn loc(&self) -> &Location {
+        match self {
+            ParseError::UnmatchedParen { loc, .. }
+            | ParseError::UnknownIdent { loc, .. }
+            | ParseError::EmptyStatement { loc } => loc,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnmatchedParen { loc, paren } => {
+                let side = if matches!(paren, '(' | '[') {
+                    "opening"
+                } else {
+                    "closing"
+                };
+                let kind = if matches!(paren, '[' | ']') {
+                    "bracket"
+                } else {
+                    "parentheses"
+                };
+                write!(f, "{loc} - Unmatched {side} {kind}!")
+            }
+            ParseError::UnknownIdent { name, loc } => {
+                write!(f, "{loc} - Unknown identifier `{name}`!")
+            }
+            ParseError::EmptyStatement { loc } => {
+                write!(f, "{loc} - Empty statements are not allowed!")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for LispErrors {
+    fn from(err: ParseError) -> Self {
+        Self {
+            errs: vec![(err.to_string(), Vec::new(), Some(err))],
+        }
+    }
 }
 
 impl Display for LispErrors {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for err in &self.errs {
+        for (i, err) in self.errs.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
             write!(f, "{}", err.0)?;
             for note in &err.1 {
                 write!(f, "\n\t{}", note)?;
@@ -26,12 +87,12 @@ impl LispErrors {
         Self { errs: Vec::new() }
     }
     pub fn error<T: Display>(mut self, loc: &Location, err: T) -> Self {
-        self.errs.push((format!("{loc} - {err}"), Vec::new()));
+        self.errs.push((format!("{loc} - {err}"), Vec::new(), None));
         self
     }
     pub fn note<'a, T: Display, L: Into<Option<&'a Location>>>(mut self, loc: L, err: T) -> Self {
         let loc: Option<&Location> = loc.into();
-        if let Some((_, notes)) = self.errs.last_mut() {
+        if let Some((_, notes, _)) = self.errs.last_mut() {
             let msg = if let Some(l) = loc {
                 format!("NOTE: {l} - {err}")
             } else {
@@ -41,6 +102,13 @@ impl LispErrors {
         }
         self
     }
+    // The parse errors among these errors, in the order they were found.
+    pub fn parse_errors(&self) -> impl Iterator<Item = &ParseError> {
+        self.errs.iter().filter_map(|(_, _, kind)| kind.as_ref())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.errs.is_empty()
+    }
     pub fn extend(&mut self, other: Self) {
         self.errs.extend(other.errs)
     }
@@ -1,7 +1,8 @@
-use error::LispErrors;
-
-use crate::ast::{make_ast, Scope, Var};
-use crate::tokens::{tokenize, Location};
+pub use crate::ast::{make_ast, make_program, set_max_depth, Scope, Statement, Var};
+pub use crate::callable::{Arity, Callable, CallableClone, MultiMethod, ReadLine, TimeNow};
+pub use crate::error::{LispErrors, ParseError};
+pub use crate::tokens::{tokenize, Location, Token};
+pub use crate::types::LispType;
 
 mod ast;
 mod callable;
@@ -9,141 +10,2038 @@ mod error;
 mod tokens;
 mod types;
 
-pub fn run_lisp(source: &str, file: &str) -> Result<String, LispErrors> {
+fn start_of(file: &str) -> Location {
+    Location {
+        filename: file.to_string(),
+        col: 0,
+        line: 0,
+    }
+}
+
+// Runs each top-level statement in order, and gives back what the last one evaluates to. Running
+// stops at the first error; `run_lisp_all` keeps going instead.
+pub fn run_lisp(source: &str, file: &str) -> Result<Var, LispErrors> {
     let toks = tokenize(source, file.to_string())?;
-    let ast = make_ast(
-        &toks,
-        &mut Scope::default(),
-        &Location {
-            filename: file.to_string(),
-            col: 0,
-            line: 0,
-        },
-    )?;
-    Ok(format!("{}", ast.resolve()?))
+    let program = make_program(&toks, &mut Scope::default(), &start_of(file))?;
+    run_program(&program)
+}
+
+fn run_program(program: &[Statement]) -> Result<Var, LispErrors> {
+    let mut res = Var::new(LispType::Nil);
+    for statement in program {
+        res = statement.resolve()?;
+    }
+    Ok(res)
+}
+
+// What happened to each top-level statement, in the order they are written.
+#[derive(Debug)]
+pub struct RunSummary {
+    pub results: Vec<Result<Var, LispErrors>>,
+}
+
+impl RunSummary {
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|r| r.is_ok()).count()
+    }
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+    // The errors from every statement that failed, in order.
+    pub fn errors(&self) -> LispErrors {
+        let mut errs = LispErrors::new();
+        for e in self.results.iter().filter_map(|r| r.as_ref().err()) {
+            errs.extend(e.clone());
+        }
+        errs
+    }
+}
+
+// Runs every top-level statement, even after one of them fails, which is useful for a file of
+// independent statements (like tests). Mistakes found while parsing still stop it before anything
+// is run.
+pub fn run_lisp_all(source: &str, file: &str) -> Result<RunSummary, LispErrors> {
+    let toks = tokenize(source, file.to_string())?;
+    let program = make_program(&toks, &mut Scope::default(), &start_of(file))?;
+    Ok(RunSummary {
+        results: program.iter().map(Statement::resolve).collect(),
+    })
+}
+
+// Parses the source and looks for mistakes, without running it.
+pub fn check_lisp(source: &str, file: &str) -> Result<(), LispErrors> {
+    let toks = tokenize(source, file.to_string())?;
+    let program = make_program(&toks, &mut Scope::default(), &start_of(file))?;
+    let mut errs = LispErrors::new();
+    for statement in &program {
+        if let Err(e) = statement.validate() {
+            errs.extend(e);
+        }
+    }
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs)
+    }
 }
 
 #[cfg(feature = "debug")]
-pub fn run_lisp_dumped(source: &str, file: &str) -> Result<String, LispErrors> {
+pub fn run_lisp_dumped(source: &str, file: &str) -> Result<Var, LispErrors> {
     let toks = tokenize(source, file.to_string())?;
     for tok in &toks {
         println!("{} => {:?}", tok.loc, tok.dat);
     }
-    let ast = make_ast(
-        &toks,
-        &mut Scope::default(),
-        &Location {
-            filename: file.to_string(),
-            col: 0,
-            line: 0,
-        },
-    )?;
-    println!("Ast = {ast:#?}");
-    Ok(format!("{}", ast.resolve()?))
+    let program = make_program(&toks, &mut Scope::default(), &start_of(file))?;
+    println!("Ast = {program:#?}");
+    run_program(&program)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        io::{self, Cursor, Write},
+        rc::Rc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
     use crate::{
-        run_lisp, tokenize,
+        ast::{make_ast, Scope},
+        callable::{Callable, DebugScope, IntrinsicOp, MultiMethod, ReadLine, TimeNow},
+        check_lisp,
+        error::{LispErrors, ParseError},
+        start_of, tokenize,
         tokens::{Location, Token, TokenType},
         types::LispType,
+        Var,
     };
+    // Like `run_lisp`, but with a scope that the caller controls.
+    fn run_in_scope(source: &str, scope: &mut Scope) -> Result<String, LispErrors> {
+        let toks = tokenize(source, "-".to_string())?;
+        let ast = make_ast(&toks, scope, &toks[0].loc)?;
+        Ok(format!("{}", ast.resolve()?))
+    }
+
+    // Runs the source, but keeps the result as a value instead of a string.
+    fn run_in_var(source: &str) -> Var {
+        let toks = tokenize(source, "-".to_string()).unwrap();
+        let ast = make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap();
+        ast.resolve().unwrap()
+    }
+
+    // Fails the test if it is ever called, for checking that something isn't run.
+    #[derive(Debug, Clone)]
+    struct Boom;
+    impl Callable for Boom {
+        fn call(&self, _args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+            Err(LispErrors::new().error(loc_called, "This should not run!"))
+        }
+    }
+
+    // Counts how many times it has been called, and gives back the count.
+    #[derive(Debug, Clone)]
+    struct Tick(Rc<Cell<isize>>);
+    impl Callable for Tick {
+        fn call(&self, _args: &[Var], _loc_called: &Location) -> Result<Var, LispErrors> {
+            self.0.set(self.0.get() + 1);
+            Ok(Var::new(self.0.get()))
+        }
+    }
+
+    // Writes to a buffer that the test can still read once it has been handed over.
+    #[derive(Clone, Default)]
+    struct Captured(Rc<RefCell<Vec<u8>>>);
+    impl Captured {
+        fn take(&self) -> String {
+            String::from_utf8(self.0.take()).unwrap()
+        }
+    }
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Most tests only care about what the result prints as.
+    fn run_lisp(source: &str, file: &str) -> Result<String, LispErrors> {
+        crate::run_lisp(source, file).map(|v| v.to_string())
+    }
+
     #[test]
     fn test_tokenizer() {
+        let at = |col| Location {
+            col,
+            ..start_of("-")
+        };
+        let tok = |col, end, dat| Token {
+            loc: at(col),
+            end: at(end),
+            dat,
+        };
         let expected_res = [
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 0,
-                },
-                dat: TokenType::StartStmt,
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 1,
-                },
-                dat: TokenType::Ident("+".to_string()),
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 3,
-                },
-                dat: TokenType::StartStmt,
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 4,
-                },
-                dat: TokenType::Ident("-".to_string()),
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 6,
-                },
-                dat: TokenType::Recognizable(LispType::Integer(1)),
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 8,
-                },
-                dat: TokenType::Recognizable(LispType::Integer(23)),
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 11,
-                },
-                dat: TokenType::Recognizable(LispType::Integer(23423423)),
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 19,
-                },
-                dat: TokenType::EndStmt,
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 20,
-                },
-                dat: TokenType::Ident("\"sliijioo\"".to_string()),
-            },
-            Token {
-                loc: Location {
-                    filename: "-".to_string(),
-                    line: 0,
-                    col: 31,
-                },
-                dat: TokenType::EndStmt,
-            },
+            tok(0, 1, TokenType::StartStmt),
+            tok(1, 2, TokenType::Ident("+".to_string())),
+            tok(3, 4, TokenType::StartStmt),
+            tok(4, 5, TokenType::Ident("-".to_string())),
+            tok(6, 7, TokenType::Recognizable(LispType::Integer(1))),
+            tok(8, 10, TokenType::Recognizable(LispType::Integer(23))),
+            tok(11, 19, TokenType::Recognizable(LispType::Integer(23423423))),
+            tok(19, 20, TokenType::EndStmt),
+            tok(
+                21,
+                31,
+                TokenType::Recognizable(LispType::Str("sliijioo".to_string())),
+            ),
+            tok(31, 32, TokenType::EndStmt),
         ];
         assert_eq!(
             Ok(expected_res.to_vec()),
-            tokenize("(+ (- 1 23 23423423) \"sliijioo\")", "-")
+            tokenize("(+ (- 1 23 23423423) \"sliijioo\")", "-".to_string())
         );
     }
     #[test]
     fn test_addition() {
         let source = "(+ 34 (+ 34 1))";
+        assert_eq!(run_lisp(source, "<provided>").unwrap(), "69");
+    }
+    #[test]
+    fn test_multimethod_dispatch() {
+        #[derive(Debug, Clone)]
+        struct Describe(&'static str);
+        impl Callable for Describe {
+            fn call(&self, _args: &[Var], _loc_called: &Location) -> Result<Var, LispErrors> {
+                Ok(Var::new(self.0))
+            }
+        }
+        let mut method = MultiMethod::new();
+        method.register("integer", Describe("an integer"));
+        method.register("string", Describe("a string"));
+        let loc = start_of("-");
+        assert_eq!(
+            *method.call(&[Var::new(1isize)], &loc).unwrap().get(),
+            LispType::Str("an integer".to_string())
+        );
+        assert_eq!(
+            *method.call(&[Var::new("hi")], &loc).unwrap().get(),
+            LispType::Str("a string".to_string())
+        );
+        assert!(method.call(&[Var::new(1.5)], &loc).is_err());
+    }
+    #[test]
+    fn test_deep_equal() {
+        let lhs = LispType::List(vec![Var::new(1isize), Var::new(2.0)]);
+        let rhs = LispType::List(vec![Var::new(1.0), Var::new(2isize)]);
+        assert_eq!(lhs, rhs);
+        let nested = LispType::List(vec![Var::new(LispType::List(vec![Var::new(3isize)]))]);
+        let other = LispType::List(vec![Var::new(LispType::List(vec![Var::new(3.0)]))]);
+        assert_eq!(nested, other);
+        assert_ne!(lhs, nested);
+        assert_eq!(run_lisp("(deep-equal 2 2.0)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(deep-equal 2 \"2\")", "-").unwrap(), "false");
+        assert_eq!(
+            run_lisp("(deep-equal (list 1 2.0) (list 1.0 2))", "-").unwrap(),
+            "true"
+        );
+
+        // Unlike `=`, numbers only have to be close, even inside lists.
+        assert_ne!(LispType::Floating(1.0005), LispType::Floating(1.0));
+        assert!(LispType::Floating(1.0005).deep_eq(&LispType::Floating(1.0)));
+        assert_eq!(run_lisp("(= (+ 0.1 0.2) 0.3)", "-").unwrap(), "false");
+        assert_eq!(
+            run_lisp("(deep-equal (+ 0.1 0.2) 0.3)", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(run_lisp("(= [1 [1.0005]] [1 [1]])", "-").unwrap(), "false");
+        assert_eq!(
+            run_lisp("(deep-equal [1 [1.0005]] [1 [1]])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal [1 [1.5]] [1 [1]])", "-").unwrap(),
+            "false"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal [1 2] [1 2 3])", "-").unwrap(),
+            "false"
+        );
+
+        // Lists that contain themselves are compared without recursing forever.
+        let cyclic = |last: f64| {
+            let list = Var::new(LispType::List(vec![Var::new(last)]));
+            if let LispType::List(items) = &mut *list.get_mut() {
+                items.push(list.new_ref());
+            }
+            list
+        };
+        assert!(cyclic(1.0).get().deep_eq(&cyclic(1.0005).get()));
+        assert!(!cyclic(1.0).get().deep_eq(&cyclic(2.0).get()));
+        assert_ne!(cyclic(1.0), cyclic(1.0005));
+    }
+    #[test]
+    fn test_list_literal() {
+        let toks = tokenize("(deep-equal [1 2 3] [])", "-".to_string()).unwrap();
+        let kinds: Vec<TokenType> = toks.into_iter().map(|t| t.dat).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenType::StartStmt,
+                TokenType::Ident("deep-equal".to_string()),
+                TokenType::OpenBracket,
+                TokenType::Recognizable(LispType::Integer(1)),
+                TokenType::Recognizable(LispType::Integer(2)),
+                TokenType::Recognizable(LispType::Integer(3)),
+                TokenType::CloseBracket,
+                TokenType::OpenBracket,
+                TokenType::CloseBracket,
+                TokenType::EndStmt,
+            ]
+        );
+        let toks = tokenize("(deep-equal [1 2 3] [])", "-".to_string()).unwrap();
+        let ast = make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap();
+        assert_eq!(
+            *ast.args[0].get(),
+            LispType::List(vec![Var::new(1isize), Var::new(2isize), Var::new(3isize)])
+        );
+        assert_eq!(*ast.args[1].get(), LispType::List(Vec::new()));
+        assert_eq!(
+            run_lisp("(deep-equal [1 [2 3]] [1.0 [2 3.0]])", "-").unwrap(),
+            "true"
+        );
+        assert!(run_lisp("(deep-equal [1 2) [])", "-").is_err());
+        assert!(run_lisp("(deep-equal (+ 1 2] [])", "-").is_err());
+    }
+    #[test]
+    fn test_print_arity_error_location() {
+        let err = run_lisp("(print 1 2 3)", "-").unwrap_err().to_string();
+        let toks = tokenize("(print 1 2 3)", "-".to_string()).unwrap();
+        assert!(err.starts_with(&format!("{} - ", toks[1].loc)));
+        assert!(err.contains("but 3 were given"));
+    }
+    #[test]
+    fn test_number_type_predicates() {
+        assert_eq!(run_lisp("(integer? 3)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(integer? 3.0)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(float? 3.0)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(float? 3)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(integer? (+ 1 2))", "-").unwrap(), "true");
+        assert!(run_lisp("(float? 1 2)", "-").is_err());
+    }
+    #[test]
+    fn test_string_lengths() {
+        assert_eq!(run_lisp("(strlen \"hello\")", "-").unwrap(), "5");
+        assert_eq!(run_lisp("(byte-length \"hello\")", "-").unwrap(), "5");
+        assert_eq!(run_lisp("(strlen \"é\")", "-").unwrap(), "1");
+        assert_eq!(run_lisp("(byte-length \"é\")", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(strlen \"日本\")", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(byte-length \"日本\")", "-").unwrap(), "6");
+        assert!(run_lisp("(strlen 5)", "-").is_err());
+    }
+    #[test]
+    fn test_partition() {
+        assert_eq!(
+            run_lisp(
+                "(deep-equal (partition integer? [1 2.5 3 4.5 (+ 2 3)]) [[1 3 5] [2.5 4.5]])",
+                "-"
+            )
+            .unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (partition float? []) [[] []])", "-").unwrap(),
+            "true"
+        );
+        assert!(run_lisp("(partition 1 [1 2])", "-").is_err());
+        assert!(run_lisp("(partition integer? 1)", "-").is_err());
+    }
+    #[test]
+    fn test_scope_completions() {
+        let mut scope = Scope::default();
+        for name in ["bar", "baz", "barrel"] {
+            scope.vars.insert(name.to_string(), Var::new(LispType::Nil));
+        }
+        assert_eq!(scope.completions("bar"), ["bar", "barrel"]);
+        assert_eq!(scope.completions("ba"), ["bar", "barrel", "baz"]);
+        assert_eq!(scope.completions("float"), ["float?"]);
+        assert!(scope.completions("qux").is_empty());
+    }
+    #[test]
+    fn test_integer_division() {
+        assert_eq!(run_lisp("(quotient 7 2)", "-").unwrap(), "3");
+        assert_eq!(run_lisp("(quotient -7 2)", "-").unwrap(), "-3");
+        assert_eq!(run_lisp("(remainder -7 3)", "-").unwrap(), "-1");
+        assert_eq!(run_lisp("(modulo -7 3)", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(remainder 7 -3)", "-").unwrap(), "1");
+        assert_eq!(run_lisp("(modulo 7 -3)", "-").unwrap(), "-2");
+        assert_eq!(run_lisp("(modulo 6 3)", "-").unwrap(), "0");
+        for op in ["quotient", "remainder", "modulo", "%"] {
+            let err = run_lisp(&format!("({op} 1 0)"), "-").unwrap_err();
+            assert!(
+                err.to_string().contains(&format!("`{op}` by zero")),
+                "{err}"
+            );
+        }
+        assert!(run_lisp("(modulo 1.5 2)", "-").is_err());
+    }
+    #[test]
+    fn test_bitwise() {
+        assert_eq!(run_lisp("(bit-and 12 10)", "-").unwrap(), "8");
+        assert_eq!(run_lisp("(bit-or 12 10)", "-").unwrap(), "14");
+        assert_eq!(run_lisp("(bit-xor 12 10)", "-").unwrap(), "6");
+        assert_eq!(run_lisp("(bit-not 0)", "-").unwrap(), "-1");
+        assert_eq!(run_lisp("(shl 1 4)", "-").unwrap(), "16");
+        assert_eq!(run_lisp("(shr 16 2)", "-").unwrap(), "4");
+        assert_eq!(run_lisp("(shr -16 2)", "-").unwrap(), "-4");
+        assert!(run_lisp("(shl 1 -1)", "-").is_err());
+        assert!(run_lisp("(shl 1 1000)", "-").is_err());
+        assert!(run_lisp("(bit-and 1 1.0)", "-").is_err());
+    }
+    #[test]
+    fn test_check_arity() {
+        let err = check_lisp("(+ (print 1 2 3) (strlen))", "-").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("This function takes exactly 1 argument, but 3 were given!"));
+        assert!(msg.contains("This function takes exactly 1 argument, but 0 were given!"));
+        assert!(check_lisp("(+ [(bit-not 1 2)] 1)", "-").is_err());
+        assert_eq!(check_lisp("(+ (strlen \"abc\") 1)", "-"), Ok(()));
+    }
+    #[test]
+    fn test_interleave() {
+        assert_eq!(
+            run_lisp(
+                "(deep-equal (interleave [1 2 3] [\"a\" \"b\" \"c\"]) [1 \"a\" 2 \"b\" 3 \"c\"])",
+                "-"
+            )
+            .unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (interleave [1 2 3] [4]) [1 4])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (interleave [] [4 5]) [])", "-").unwrap(),
+            "true"
+        );
+        assert!(run_lisp("(interleave [1] 2)", "-").is_err());
+    }
+    #[test]
+    fn test_self_referential_list() {
+        let list = Var::new(LispType::List(vec![Var::new(1isize)]));
+        if let LispType::List(items) = &mut *list.get_mut() {
+            items.push(list.new_ref());
+        }
+        assert_eq!(list.to_string(), "(1 ...)");
+        let other = Var::new(LispType::List(vec![Var::new(1isize)]));
+        if let LispType::List(items) = &mut *other.get_mut() {
+            items.push(other.new_ref());
+        }
+        assert_eq!(list, list.new_ref());
+        assert_eq!(list, other);
+        assert_eq!(*list.get(), *other.get());
+    }
+    #[test]
+    fn test_flat_let() {
+        assert_eq!(run_lisp("(let ((x 1) (y 2)) + x y)", "-").unwrap(), "3");
+        assert_eq!(run_lisp("(let (x 1 y 2) + x y)", "-").unwrap(), "3");
+        assert_eq!(run_lisp("(let (x 1 y +) y x x)", "-").unwrap(), "2");
+        let err = run_lisp("(let (x 1 y) + x y)", "-")
+            .unwrap_err()
+            .to_string();
+        let toks = tokenize("(let (x 1 y) + x y)", "-".to_string()).unwrap();
+        assert!(err.starts_with(&format!("{} - Variable \"y\" has no value!", toks[5].loc)));
+        assert!(run_lisp("(let (1 x) + x x)", "-").is_err());
+
+        // Names that are already bound are values, even without a literal to give it away.
+        let mut scope = Scope::default();
+        run_in_scope("(let y 3)", &mut scope).unwrap();
+        assert_eq!(run_in_scope("(let (x y) + x 0)", &mut scope).unwrap(), "3");
+        assert_eq!(
+            run_in_scope("(let (a y b +) b a a)", &mut scope).unwrap(),
+            "6"
+        );
+    }
+    #[test]
+    fn test_to_list() {
+        assert_eq!(
+            run_lisp("(deep-equal (to-list \"abé\") [\"a\" \"b\" \"é\"])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (to-list \"\") [])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (to-list [1 [2]]) [1 [2]])", "-").unwrap(),
+            "true"
+        );
+        assert!(run_lisp("(to-list 5)", "-").is_err());
+    }
+    #[test]
+    fn test_time_now() {
+        let mut scope = Scope::default();
+        scope.vars.insert(
+            "time-now".to_string(),
+            Var::new(TimeNow::with_clock(|| {
+                UNIX_EPOCH + Duration::from_secs(1_650_000_000)
+            })),
+        );
+        assert_eq!(
+            run_in_scope("(+ (time-now) 5)", &mut scope).unwrap(),
+            "1650000005"
+        );
+        assert!(run_lisp("(integer? (time-now))", "-").is_ok());
+    }
+    #[test]
+    fn test_debug_scope() {
+        let mut scope = Scope::default();
+        assert_eq!(
+            run_in_scope("(let (x 1 y \"two\") debug-scope)", &mut scope).unwrap(),
+            "nil"
+        );
+
+        let out = Captured::default();
+        let debug = DebugScope::with_writer(scope.snapshot(), out.clone());
+        let loc = Location {
+            line: 2,
+            col: 4,
+            ..start_of("-")
+        };
+        assert_eq!(*debug.call(&[], &loc).unwrap().get(), LispType::Nil);
+        let printed = out.take();
+        assert!(printed.starts_with(&format!("{loc} - Scope:\n")));
+        assert!(printed.contains("x = 1\n"));
+        assert!(printed.contains("y = two\n"));
+        assert!(printed.contains("print = <Function>\n"));
+    }
+    #[test]
+    fn test_split_at() {
+        assert_eq!(
+            run_lisp("(deep-equal (split-at [1 2 3 4] 2) [[1 2] [3 4]])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (split-at [1 2 3 4] 10) [[1 2 3 4] []])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (split-at [1 2] 0) [[] [1 2]])", "-").unwrap(),
+            "true"
+        );
+        let err = run_lisp("(split-at [1 2] -1)", "-").unwrap_err();
+        assert!(err.to_string().contains("negative index"));
+        assert!(run_lisp("(split-at 1 1)", "-").is_err());
+    }
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(
+            run_lisp(
+                "(wrap-text \"the quick brown fox jumps over the lazy dog\" 10)",
+                "-"
+            )
+            .unwrap(),
+            "the quick\nbrown fox\njumps over\nthe lazy\ndog"
+        );
+        assert_eq!(
+            run_lisp("(wrap-text \"fits on one line\" 40)", "-").unwrap(),
+            "fits on one line"
+        );
+        assert_eq!(
+            run_lisp("(wrap-text \"a enormous b\" 4)", "-").unwrap(),
+            "a\nenormous\nb"
+        );
+        assert!(run_lisp("(wrap-text \"abc\" 0)", "-").is_err());
+        assert!(run_lisp("(wrap-text 5 5)", "-").is_err());
+    }
+    #[test]
+    fn test_divide_and_modulo() {
+        assert_eq!(run_lisp("(/ 10 3)", "-").unwrap(), "3");
+        assert_eq!(run_lisp("(/ 100 5 2)", "-").unwrap(), "10");
+        assert_eq!(run_lisp("(% 10 3)", "-").unwrap(), "1");
+        let toks = tokenize("(/ 10 0)", "-".to_string()).unwrap();
+        let err = run_lisp("(/ 10 0)", "-").unwrap_err().to_string();
+        assert_eq!(err, format!("{} - Division by zero!", toks[1].loc));
+        assert!(run_lisp("(% 10 0)", "-").is_err());
+        assert!(run_lisp("(/ 10 \"2\")", "-").is_err());
+    }
+    #[test]
+    fn test_car_cdr_compositions() {
+        assert_eq!(run_lisp("(cadr [1 2 3])", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(caddr [1 2 3])", "-").unwrap(), "3");
+        assert_eq!(
+            run_lisp("(deep-equal (cddr [1 2 3]) [3])", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(
+            run_lisp("(deep-equal (cdar [[1 2] 3]) [2])", "-").unwrap(),
+            "true"
+        );
+        let err = run_lisp("(caddr [1 2])", "-").unwrap_err();
+        assert!(err.to_string().contains("too short"));
+        assert!(run_lisp("(cdar [1 2])", "-").is_err());
+    }
+    #[test]
+    fn test_mixed_arithmetic() {
+        assert_eq!(*run_in_var("(+ 1 2.5)").get(), LispType::Floating(3.5));
+        assert_eq!(*run_in_var("(* 2 1.5)").get(), LispType::Floating(3.0));
+        assert_eq!(*run_in_var("(- 5 0.5)").get(), LispType::Floating(4.5));
+        assert_eq!(*run_in_var("(- 5 2)").get(), LispType::Integer(3));
+        assert_eq!(run_lisp("(- 10 0.5 2)", "-").unwrap(), "7.5");
+        assert!(run_lisp("(+ 1 \"x\")", "-").is_err());
+    }
+    #[test]
+    fn test_if() {
+        assert_eq!(run_lisp("(if 1 (+ 1 1) (+ 2 2))", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(if nil 1 2)", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(if 0 1 2)", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(if (integer? 1.5) 1)", "-").unwrap(), "nil");
+        assert_eq!(run_lisp("(+ 1 (if \"\" 1 2))", "-").unwrap(), "2");
+        // The branch that isn't picked is never run.
+        assert_eq!(run_lisp("(if 1 2 (/ 1 0))", "-").unwrap(), "2");
+        assert!(run_lisp("(if 1)", "-").is_err());
+    }
+    #[test]
+    fn test_clamp() {
+        assert_eq!(*run_in_var("(clamp -5 0 10)").get(), LispType::Integer(0));
+        assert_eq!(*run_in_var("(clamp 5 0 10)").get(), LispType::Integer(5));
+        assert_eq!(*run_in_var("(clamp 15 0 10)").get(), LispType::Integer(10));
+        assert_eq!(
+            *run_in_var("(clamp 0.5 0 10)").get(),
+            LispType::Floating(0.5)
+        );
+        assert_eq!(
+            *run_in_var("(clamp 11 0 9.5)").get(),
+            LispType::Floating(9.5)
+        );
+        let err = run_lisp("(clamp 1 10 0)", "-").unwrap_err();
+        assert!(err.to_string().contains("greater than the upper bound"));
+        assert!(run_lisp("(clamp \"a\" 0 1)", "-").is_err());
+    }
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(*run_in_var("(< 1 2)").get(), LispType::Bool(true));
+        assert_eq!(*run_in_var("(= 3 3)").get(), LispType::Bool(true));
+        assert_eq!(run_lisp("(> 1 2)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(<= 2 2)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(>= 1 2)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(= 2 2.0)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(< 1 1.5)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(if (< 1 2) 10 20)", "-").unwrap(), "10");
+        assert!(run_lisp("(< 1 \"2\")", "-").is_err());
+        // None of them allow for rounding errors; only `deep-equal` does.
+        assert_eq!(run_lisp("(< 0.0001 0.0005)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(> 1.0005 1.0)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(<= 1.0005 1.0)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(= 1.0005 1.0)", "-").unwrap(), "false");
+    }
+    #[test]
+    fn test_equality_of_strings_and_lists() {
+        assert_eq!(run_lisp("(= \"abc\" \"abc\")", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(= \"abc\" \"abd\")", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(= (list 1 2) (list 1 2))", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(= [1 [2 3]] [1 [2 3]])", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(= [1 2] [1 2 3])", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(= 1 \"1\")", "-").unwrap(), "false");
+        assert!(run_lisp("(= 1)", "-").is_err());
+    }
+    #[test]
+    fn test_spread() {
+        assert_eq!(run_lisp("(spread + 1 [2 3])", "-").unwrap(), "6");
+        assert_eq!(run_lisp("(spread + [1 2 3])", "-").unwrap(), "6");
+        assert_eq!(run_lisp("(spread - 10 (+ 1 1) [3])", "-").unwrap(), "5");
+        assert!(run_lisp("(spread 1 [2 3])", "-").is_err());
+        assert!(run_lisp("(spread + 1 2)", "-").is_err());
+    }
+
+    #[test]
+    fn test_let_binding_form() {
+        let mut scope = Scope::default();
+        assert_eq!(run_in_scope("(let x 5)", &mut scope), Ok("5".to_string()));
+        assert_eq!(run_in_scope("(* x 2)", &mut scope), Ok("10".to_string()));
+        assert_eq!(
+            run_lisp("(+ (let y 5) y)", "test.lisp"),
+            Ok("10".to_string())
+        );
+        assert_eq!(
+            run_lisp("(+ (let (z 1) + z 1) 1)", "test.lisp"),
+            Ok("3".to_string())
+        );
+        let err = run_lisp("(+ w 1)", "test.lisp").unwrap_err().to_string();
+        assert!(err.contains("Unknown identifier `w`"), "{err}");
+        assert!(run_lisp("(let x (+ 1 2))", "test.lisp").is_err());
+        assert!(run_lisp("(let)", "test.lisp").is_err());
+    }
+
+    #[test]
+    fn test_const_fold() {
+        let toks = tokenize("(* (+ 1 2) (- 10 (* 2 2)))", "-".to_string()).unwrap();
+        let mut ast = make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap();
+        ast.const_fold();
+        assert_eq!(*ast.args[0].get(), LispType::Integer(3));
+        assert_eq!(*ast.args[1].get(), LispType::Integer(6));
+        assert_eq!(format!("{}", ast.resolve().unwrap()), "18");
+
+        let toks = tokenize("(+ (let x 1) (print x) (+ x 1) (/ 1 0))", "-".to_string()).unwrap();
+        let mut ast = make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap();
+        ast.const_fold();
+        for arg in &ast.args[1..] {
+            assert!(matches!(*arg.get(), LispType::Statement(_)));
+        }
+
+        // Only arithmetic and comparisons are folded, even though strings are pure too.
+        let toks = tokenize(
+            "(print (+ 1 2) (concat \"a\" \"b\") (len \"abc\"))",
+            "-".to_string(),
+        )
+        .unwrap();
+        let mut ast = make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap();
+        ast.const_fold();
+        assert_eq!(*ast.args[0].get(), LispType::Integer(3));
+        for arg in &ast.args[1..] {
+            assert!(matches!(*arg.get(), LispType::Statement(_)));
+        }
+    }
+
+    #[test]
+    fn test_semicolon_comments() {
+        let kinds = |source: &str| -> Vec<TokenType> {
+            tokenize(source, "-".to_string())
+                .unwrap()
+                .into_iter()
+                .map(|t| t.dat)
+                .collect()
+        };
+        assert_eq!(kinds("(+ 1 ; add them\n 2)"), kinds("(+ 1 2)"));
         assert_eq!(
-            *run_lisp(source, "<provided>").unwrap().get(),
-            LispType::Integer(69)
+            run_lisp("(+ 1 ; one\n (* 2 3)) ; the end of (+ 1 ...)", "test.lisp"),
+            Ok("7".to_string())
         );
+        assert_eq!(
+            run_lisp("(strlen \"a;b\")", "test.lisp"),
+            Ok("3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_number() {
+        let run = |source| run_lisp(source, "test.lisp");
+        assert_eq!(run("(format-number 1234567)"), Ok("1,234,567".to_string()));
+        assert_eq!(run("(format-number 123)"), Ok("123".to_string()));
+        assert_eq!(
+            run("(format-number -1234567)"),
+            Ok("-1,234,567".to_string())
+        );
+        assert_eq!(run("(format-number -123456)"), Ok("-123,456".to_string()));
+        assert_eq!(run("(format-number 1234.5)"), Ok("1,234.5".to_string()));
+        assert_eq!(
+            run("(format-number 1234567 \"_\")"),
+            Ok("1_234_567".to_string())
+        );
+        assert!(run("(format-number \"12\")").is_err());
+    }
+
+    #[test]
+    fn test_lazy_runs_once() {
+        let ticks = Rc::new(Cell::new(0));
+        let mut scope = Scope::default();
+        scope
+            .vars
+            .insert("tick".to_string(), Var::new(Tick(Rc::clone(&ticks))));
+        let toks = tokenize("(lazy (tick))", "-".to_string()).unwrap();
+        let promise = make_ast(&toks, &mut scope, &toks[0].loc)
+            .unwrap()
+            .resolve()
+            .unwrap();
+        assert_eq!(ticks.get(), 0);
+        scope.vars.insert("p".to_string(), promise);
+        assert_eq!(
+            run_in_scope("(+ (force p) (force p))", &mut scope),
+            Ok("2".to_string())
+        );
+        assert_eq!(ticks.get(), 1);
+        assert_eq!(run_lisp("(force 5)", "test.lisp"), Ok("5".to_string()));
+    }
+
+    #[test]
+    fn test_type_errors_have_locations() {
+        // There is no separate `TypeError`; type errors are `LispErrors` that always carry the
+        // location of the call.
+        let err = run_lisp("(+ 1 \"x\")", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.lisp:0:"), "{err}");
+        assert!(err.contains("Incompatible types for addition"), "{err}");
+        let err = run_lisp("(* 2 (- 1 \"x\"))", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.lisp:0:"), "{err}");
+        assert!(err.contains("Incompatible types for subtraction"), "{err}");
+    }
+
+    #[test]
+    fn test_starts_and_ends_with() {
+        let run = |source| run_lisp(source, "test.lisp");
+        assert_eq!(
+            run("(starts-with? \"hello\" \"he\")"),
+            Ok("true".to_string())
+        );
+        assert_eq!(
+            run("(starts-with? \"hello\" \"lo\")"),
+            Ok("false".to_string())
+        );
+        assert_eq!(run("(ends-with? \"hello\" \"lo\")"), Ok("true".to_string()));
+        assert_eq!(
+            run("(ends-with? \"hello\" \"he\")"),
+            Ok("false".to_string())
+        );
+        let err = run("(ends-with? \"hello\" 1)").unwrap_err().to_string();
+        assert!(
+            err.contains("`ends-with?` requires strings, not integer"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_user_defined_functions() {
+        let mut scope = Scope::default();
+        run_in_scope("(fn square (x) (* x x))", &mut scope).unwrap();
+        assert_eq!(run_in_scope("(square 5)", &mut scope), Ok("25".to_string()));
+        assert_eq!(
+            run_in_scope("(+ (square 2) (square 3))", &mut scope),
+            Ok("13".to_string())
+        );
+        run_in_scope(
+            "(fn fact (n) (if (<= n 1) 1 (* n (fact (- n 1)))))",
+            &mut scope,
+        )
+        .unwrap();
+        assert_eq!(run_in_scope("(fact 5)", &mut scope), Ok("120".to_string()));
+        let err = run_in_scope("(square 1 2)", &mut scope)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("takes exactly 1 argument"), "{err}");
+        assert!(run_lisp("(fn bad (1) 1)", "test.lisp").is_err());
+    }
+
+    #[test]
+    fn test_deftest_and_run_tests() {
+        let out = Captured::default();
+        let mut scope = Scope::with_test_output(out.clone());
+        run_in_scope("(deftest \"adds\" (assert (= (+ 1 1) 2)))", &mut scope).unwrap();
+        run_in_scope(
+            "(deftest \"breaks\" (assert (= (+ 1 1) 3) \"math is broken\"))",
+            &mut scope,
+        )
+        .unwrap();
+        assert!(run_in_scope("(deftest \"adds\" 1)", &mut scope).is_err());
+        assert_eq!(run_in_scope("(run-tests)", &mut scope), Ok("1".to_string()));
+        let printed = out.take();
+        assert!(printed.contains("FAIL breaks: "), "{printed}");
+        assert!(printed.contains("math is broken"), "{printed}");
+        assert!(printed.ends_with("1 passed, 1 failed\n"), "{printed}");
+        let err = run_lisp("(assert 0 \"zero\")", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Assertion failed: zero"), "{err}");
+        assert_eq!(run_lisp("(run-tests)", "test.lisp"), Ok("0".to_string()));
+    }
+    #[test]
+    fn test_deftest_inside_a_test() {
+        let out = Captured::default();
+        let mut scope = Scope::with_test_output(out.clone());
+        run_in_scope("(deftest \"outer\" (deftest \"inner\" true))", &mut scope).unwrap();
+        // The inner test is only registered while the outer one runs, so it runs the next time.
+        assert_eq!(run_in_scope("(run-tests)", &mut scope), Ok("0".to_string()));
+        assert_eq!(out.take(), "1 passed, 0 failed\n");
+        assert_eq!(run_in_scope("(run-tests)", &mut scope), Ok("1".to_string()));
+        let printed = out.take();
+        assert!(printed.contains("FAIL outer: "), "{printed}");
+        assert!(printed.ends_with("1 passed, 1 failed\n"), "{printed}");
+    }
+
+    #[test]
+    fn test_negative_literals() {
+        let toks = tokenize("(- -5 2)", "-".to_string()).unwrap();
+        assert_eq!(toks[1].dat, TokenType::Ident("-".to_string()));
+        assert_eq!(toks[2].dat, TokenType::Recognizable(LispType::Integer(-5)));
+        assert_eq!(toks[3].dat, TokenType::Recognizable(LispType::Integer(2)));
+        assert_eq!(run_lisp("(- -5 2)", "test.lisp"), Ok("-7".to_string()));
+        assert_eq!(run_lisp("(- 5 2)", "test.lisp"), Ok("3".to_string()));
+        assert_eq!(run_lisp("(* -1.5 2)", "test.lisp"), Ok("-3.0".to_string()));
+    }
+
+    #[test]
+    fn test_list_constructor() {
+        assert_eq!(
+            run_lisp("(list 1 2 3)", "test.lisp"),
+            Ok("(1 2 3)".to_string())
+        );
+        assert_eq!(
+            run_lisp("(list 1 (list 2 (+ 1 2)))", "test.lisp"),
+            Ok("(1 (2 3))".to_string())
+        );
+        assert_eq!(run_lisp("(list)", "test.lisp"), Ok("()".to_string()));
+        assert_eq!(
+            run_lisp("(deep-equal (list 1 [2]) [1 [2]])", "test.lisp"),
+            Ok("true".to_string())
+        );
+    }
+
+    #[test]
+    fn test_memoize() {
+        #[derive(Debug, Clone)]
+        struct CountedDouble(Rc<Cell<usize>>);
+        impl Callable for CountedDouble {
+            fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+                self.0.set(self.0.get() + 1);
+                IntrinsicOp::Multiply.call(&[args[0].new_ref(), Var::new(2)], loc_called)
+            }
+        }
+        let calls = Rc::new(Cell::new(0));
+        let mut scope = Scope::default();
+        scope.vars.insert(
+            "double".to_string(),
+            Var::new(CountedDouble(Rc::clone(&calls))),
+        );
+        let toks = tokenize("(memoize double)", "-".to_string()).unwrap();
+        let memoized = make_ast(&toks, &mut scope, &toks[0].loc)
+            .unwrap()
+            .resolve()
+            .unwrap();
+        scope.vars.insert("fast-double".to_string(), memoized);
+        assert_eq!(
+            run_in_scope("(+ (fast-double 21) (fast-double (+ 20 1)))", &mut scope),
+            Ok("84".to_string())
+        );
+        assert_eq!(calls.get(), 1);
+        assert_eq!(
+            run_in_scope("(fast-double 1)", &mut scope),
+            Ok("2".to_string())
+        );
+        assert_eq!(calls.get(), 2);
+        assert!(run_lisp("(memoize 1)", "test.lisp").is_err());
+
+        // Arguments have to be exactly the same, not just equal with `=`.
+        let mut scope = Scope::default();
+        scope
+            .vars
+            .insert("h".to_string(), run_in_var("(memoize (fn (x) (* x 2)))"));
+        assert_eq!(run_in_scope("(h 1.0005)", &mut scope).unwrap(), "2.001");
+        assert_eq!(run_in_scope("(h 1.0)", &mut scope).unwrap(), "2.0");
+        assert_eq!(run_in_scope("(h 1)", &mut scope).unwrap(), "2");
+        assert_eq!(
+            run_in_scope("(type-of (h 1))", &mut scope).unwrap(),
+            "integer"
+        );
+        // Functions can't be compared, so calls with them are never remembered.
+        scope
+            .vars
+            .insert("apply".to_string(), run_in_var("(memoize (fn (f) (f)))"));
+        assert_eq!(run_in_scope("(apply (fn () 1))", &mut scope).unwrap(), "1");
+        assert_eq!(run_in_scope("(apply (fn () 2))", &mut scope).unwrap(), "2");
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut scope = Scope::default();
+        scope.vars.insert(
+            "read-line".to_string(),
+            Var::new(ReadLine::with_reader(Cursor::new("first\nsecond\r\n"))),
+        );
+        assert_eq!(
+            run_in_scope("(read-line)", &mut scope),
+            Ok("first".to_string())
+        );
+        assert_eq!(
+            run_in_scope("(read-line)", &mut scope),
+            Ok("second".to_string())
+        );
+        assert_eq!(
+            run_in_scope("(read-line)", &mut scope),
+            Ok("nil".to_string())
+        );
+    }
+
+    #[test]
+    fn test_walk() {
+        let toks = tokenize("(+ 1 (* 2 (- 3 1)) (strlen \"abc\"))", "-".to_string()).unwrap();
+        let ast = make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap();
+        let mut count = 0;
+        ast.walk(&mut |_| count += 1);
+        assert_eq!(count, 4);
+
+        let list = run_in_var("(list 1 [2 (+ 3 4)])");
+        let mut arg_counts = Vec::new();
+        list.walk(&mut |s| arg_counts.push(s.args.len()));
+        assert_eq!(arg_counts, vec![2]);
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        // Test threads have small stacks, and this needs as much as the main thread would have.
+        let deep = std::thread::Builder::new()
+            .stack_size(16 * 1024 * 1024)
+            .spawn(|| {
+                let nested = |depth| "(+ 1 ".repeat(depth) + "1" + &")".repeat(depth);
+                assert_eq!(run_lisp(&nested(100), "test.lisp"), Ok("101".to_string()));
+                let err = run_lisp(&nested(5000), "test.lisp")
+                    .unwrap_err()
+                    .to_string();
+                assert!(err.contains("Maximum nesting depth exceeded"), "{err}");
+
+                let mut scope = Scope::default();
+                // Calls that are the last thing a function does don't nest, so this one isn't.
+                run_in_scope(
+                    "(fn down (n) (if (<= n 0) 0 (+ 0 (down (- n 1)))))",
+                    &mut scope,
+                )
+                .unwrap();
+                assert_eq!(run_in_scope("(down 50)", &mut scope), Ok("0".to_string()));
+                let err = run_in_scope("(down 100000)", &mut scope)
+                    .unwrap_err()
+                    .to_string();
+                assert!(err.contains("Maximum nesting depth exceeded"), "{err}");
+
+                crate::set_max_depth(10);
+                assert!(run_lisp(&nested(20), "test.lisp").is_err());
+            });
+        deep.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_arithmetic_errors_name_the_argument() {
+        let err = run_lisp("(+ 1 \"hello\")", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("number and string (argument 1)"), "{err}");
+        let err = run_lisp("(* \"a\" 2 3)", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("number and string (argument 0)"), "{err}");
+        let err = run_lisp("(- 5 4 (to-list \"ab\"))", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("number and list (argument 2)"), "{err}");
+        assert!(err.contains("That argument comes from here."), "{err}");
+    }
+
+    #[test]
+    fn test_not_and_or() {
+        let run = |source| run_lisp(source, "test.lisp");
+        assert_eq!(run("(not nil)"), Ok("true".to_string()));
+        assert_eq!(run("(not 5)"), Ok("false".to_string()));
+        assert_eq!(run("(and 1 2 3)"), Ok("3".to_string()));
+        assert_eq!(run("(and 1 0 3)"), Ok("0".to_string()));
+        assert_eq!(run("(or nil 0 7)"), Ok("7".to_string()));
+        assert_eq!(run("(or nil 0)"), Ok("0".to_string()));
+        assert_eq!(run("(and)"), Ok("true".to_string()));
+        assert_eq!(run("(or)"), Ok("false".to_string()));
+
+        // The arguments after the one that decides the result aren't run.
+        let mut scope = Scope::default();
+        scope.register("boom", Boom);
+        assert_eq!(
+            run_in_scope("(and nil (boom))", &mut scope),
+            Ok("nil".to_string())
+        );
+        assert_eq!(
+            run_in_scope("(or 1 (boom))", &mut scope),
+            Ok("1".to_string())
+        );
+        assert!(run_in_scope("(and 1 (boom))", &mut scope).is_err());
+    }
+
+    #[test]
+    fn test_columns_with_indentation() {
+        let toks = tokenize("    (strlen \"ab\")", "-".to_string()).unwrap();
+        let cols: Vec<usize> = toks.iter().map(|t| t.loc.col).collect();
+        assert_eq!(cols, vec![4, 5, 12, 16]);
+        let err = run_lisp("  (+ 1 \"x\")", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.lisp:0:3 - "), "{err}");
+    }
+
+    #[test]
+    fn test_tokens_end_at_line_breaks() {
+        let toks = tokenize("(+\n1 2)", "-".to_string()).unwrap();
+        let kinds: Vec<&TokenType> = toks.iter().map(|t| &t.dat).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &TokenType::StartStmt,
+                &TokenType::Ident("+".to_string()),
+                &TokenType::Recognizable(LispType::Integer(1)),
+                &TokenType::Recognizable(LispType::Integer(2)),
+                &TokenType::EndStmt,
+            ]
+        );
+        let lines: Vec<(usize, usize)> = toks.iter().map(|t| (t.loc.line, t.loc.col)).collect();
+        assert_eq!(lines, vec![(0, 0), (0, 1), (1, 0), (1, 2), (1, 3)]);
+        assert_eq!(run_lisp("(+ 1\n2)", "test.lisp"), Ok("3".to_string()));
+        assert_eq!(
+            run_lisp("(+ 1 // one\n2)", "test.lisp"),
+            Ok("3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let mut scope = Scope::default();
+        run_in_scope("(let x 1)", &mut scope).unwrap();
+        let captured = scope.vars["x"].new_ref();
+        run_in_scope("(fn get-x () x)", &mut scope).unwrap();
+        assert_eq!(run_in_scope("(set x 10)", &mut scope), Ok("10".to_string()));
+        assert_eq!(*captured.get(), LispType::Integer(10));
+        assert_eq!(run_in_scope("(get-x)", &mut scope), Ok("10".to_string()));
+        assert_eq!(
+            run_in_scope("(+ (set x (+ x 1)) x)", &mut scope),
+            Ok("22".to_string())
+        );
+        let err = run_in_scope("(set y 1)", &mut scope)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Cannot set `y`"), "{err}");
+        assert!(run_in_scope("(set 1 2)", &mut scope).is_err());
+    }
+
+    #[test]
+    fn test_clone_functions_and_lists() {
+        let scope = Scope::default();
+        let add = scope.vars["+"].get().clone();
+        let loc = start_of("-");
+        let res = add
+            .unwrap_func()
+            .call(&[Var::new(1), Var::new(2)], &loc)
+            .unwrap();
+        assert_eq!(*res.get(), LispType::Integer(3));
+
+        let list = run_in_var("(list 1 2)");
+        let copy = list.get().clone();
+        assert_eq!(copy, *list.get());
+        let promise = run_in_var("(lazy (+ 1 2))").get().clone();
+        assert_eq!(promise.type_name(), "promise");
+
+        let mut scope = Scope::default();
+        run_in_scope("(let x 1)", &mut scope).unwrap();
+        assert_eq!(
+            run_in_scope("(set x (list 1 2))", &mut scope),
+            Ok("(1 2)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        let run = |source| run_lisp(source, "test.lisp");
+        assert_eq!(run("(quote (+ 1 2))"), Ok("(+ 1 2)".to_string()));
+        assert_eq!(
+            run("(quote (undefined (nested \"str\") if))"),
+            Ok("(undefined (nested str) if)".to_string())
+        );
+        assert_eq!(run("(quote x)"), Ok("x".to_string()));
+        assert_eq!(run_in_var("(quote x)").get().type_name(), "symbol");
+        assert_eq!(
+            run("(deep-equal (quote (1 2)) [1 2])"),
+            Ok("true".to_string())
+        );
+        assert!(run("(quote)").is_err());
+        assert!(run("(quote a b)").is_err());
+        assert!(run("(quote ))").is_err());
+    }
+    #[test]
+    fn test_quote_shorthand() {
+        let run = |source| run_lisp(source, "-");
+        assert_eq!(run_in_var("'undefined-symbol").get().type_name(), "symbol");
+        let err = run("undefined-symbol").unwrap_err();
+        assert!(matches!(
+            err.parse_errors().next(),
+            Some(ParseError::UnknownIdent { name, .. }) if name == "undefined-symbol"
+        ));
+        assert_eq!(run("'(a (b c) [1 2])"), Ok("(a (b c) (1 2))".to_string()));
+        assert_eq!(run("(deep-equal '(1 2) [1 2])"), Ok("true".to_string()));
+        assert_eq!(run("''x"), Ok("(quote x)".to_string()));
+        assert_eq!(run("print $ type-of 'x"), Ok("symbol".to_string()));
+        // Only a `'` at the start of a token quotes anything.
+        assert_eq!(run("(let (don't 1) + don't 1)"), Ok("2".to_string()));
+        assert!(run("(+ 1 ')").is_err());
+        assert!(run("'").is_err());
+    }
+
+    #[test]
+    fn test_print_returns_its_value() {
+        assert_eq!(
+            run_lisp("(+ 1 (print 2))", "test.lisp"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run_lisp("(print (list 1 2))", "test.lisp"),
+            Ok("(1 2)".to_string())
+        );
+        // Errors aren't printed as if they were values.
+        let err = run_lisp("(print (/ 1 0))", "test.lisp")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Division by zero"), "{err}");
+    }
+    #[test]
+    fn test_integer_float_equality() {
+        assert_eq!(LispType::Integer(2), LispType::Floating(2.0));
+        assert_eq!(LispType::Floating(2.0), LispType::Integer(2));
+        assert_ne!(LispType::Integer(2), LispType::Floating(2.5));
+        assert_ne!(LispType::Floating(2.5), LispType::Integer(2));
+        assert_eq!(run_lisp("(= 2 2.0)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(= 2.0 2)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(/ 5.0 2)", "-").unwrap(), "2.5");
+        assert_eq!(run_lisp("(+ 1.5 0.5)", "-").unwrap(), "2.0");
+    }
+    #[test]
+    fn test_parse_error_messages() {
+        let loc = Location {
+            filename: "test.lisp".to_string(),
+            line: 0,
+            col: 2,
+        };
+        let unmatched = ParseError::UnmatchedParen {
+            loc: loc.clone(),
+            paren: ']',
+        };
+        assert_eq!(unmatched.loc(), &loc);
+        assert_eq!(
+            unmatched.to_string(),
+            format!("{loc} - Unmatched closing bracket!")
+        );
+        let empty = ParseError::EmptyStatement { loc: loc.clone() };
+        assert_eq!(
+            LispErrors::from(empty.clone()).to_string(),
+            format!("{loc} - Empty statements are not allowed!")
+        );
+        assert_eq!(
+            LispErrors::from(empty.clone()).parse_errors().next(),
+            Some(&empty)
+        );
+        let err = run_lisp("(+ 1 2))", "-").unwrap_err().to_string();
+        assert!(err.contains("Unmatched closing parentheses!"), "{err}");
+    }
+    #[test]
+    fn test_concat_and_len() {
+        assert_eq!(run_lisp("(concat \"foo\" \"bar\")", "-").unwrap(), "foobar");
+        assert_eq!(
+            run_lisp("(concat \"a\" \"b\" \"c\" \"d\")", "-").unwrap(),
+            "abcd"
+        );
+        let err = run_lisp("(concat \"a\" 1)", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("`concat` requires strings, not integer"),
+            "{err}"
+        );
+        assert!(run_lisp("(concat \"a\")", "-").is_err());
+        assert_eq!(run_lisp("(len \"hello\")", "-").unwrap(), "5");
+        assert_eq!(run_lisp("(len \"héllo\")", "-").unwrap(), "5");
+        assert_eq!(run_lisp("(len [1 2 3])", "-").unwrap(), "3");
+        assert_eq!(run_lisp("(len [])", "-").unwrap(), "0");
+        assert_eq!(run_lisp("(len $ concat \"ab\" \"cd\")", "-").unwrap(), "4");
+        let err = run_lisp("(len 5)", "-").unwrap_err().to_string();
+        assert!(err.contains("`len` requires a string or a list"), "{err}");
+    }
+    #[test]
+    fn test_pure_statements_are_cached() {
+        // Adds its arguments, counting how many times it is called.
+        #[derive(Debug, Clone)]
+        struct CountedAdd(Rc<Cell<usize>>, bool);
+        impl Callable for CountedAdd {
+            fn call(&self, args: &[Var], loc_called: &Location) -> Result<Var, LispErrors> {
+                self.0.set(self.0.get() + 1);
+                IntrinsicOp::Add.call(args, loc_called)
+            }
+            fn is_pure(&self) -> bool {
+                self.1
+            }
+        }
+        let calls = Rc::new(Cell::new(0));
+        let mut scope = Scope::default();
+        scope.register("pure-add", CountedAdd(Rc::clone(&calls), true));
+        scope.register("impure-add", CountedAdd(Rc::clone(&calls), false));
+        let parse = |source: &str, scope: &mut Scope| {
+            let toks = tokenize(source, "-".to_string()).unwrap();
+            make_ast(&toks, scope, &toks[0].loc).unwrap()
+        };
+
+        let ast = parse("(* (pure-add 1 2) 3)", &mut scope);
+        assert!(ast.pure);
+        for _ in 0..3 {
+            assert_eq!(*ast.resolve().unwrap().get(), LispType::Integer(9));
+        }
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+        let ast = parse("(impure-add 1 2)", &mut scope);
+        assert!(!ast.pure);
+        ast.resolve().unwrap();
+        ast.resolve().unwrap();
+        assert_eq!(calls.get(), 2);
+
+        // Variables can be changed, so statements that use them are run every time.
+        calls.set(0);
+        let ast = parse("(pure-add (let x 1) x)", &mut scope);
+        assert!(!ast.pure);
+        assert_eq!(*ast.resolve().unwrap().get(), LispType::Integer(2));
+        assert_eq!(*ast.resolve().unwrap().get(), LispType::Integer(2));
+        assert_eq!(calls.get(), 2);
+    }
+    #[test]
+    fn test_nth() {
+        assert_eq!(run_lisp("(nth 1 (list 10 20 30))", "-").unwrap(), "20");
+        assert_eq!(run_lisp("(nth 0 [10 20 30])", "-").unwrap(), "10");
+        assert_eq!(run_lisp("(nth 2 [10 20 30])", "-").unwrap(), "30");
+        let err = run_lisp("(nth 3 [10 20 30])", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("Index 3 is out of range for a list of length 3!"),
+            "{err}"
+        );
+        let err = run_lisp("(nth -1 [10 20 30])", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Index -1 is out of range for a list of length 3!"),
+            "{err}"
+        );
+        let err = run_lisp("(nth 0 \"abc\")", "-").unwrap_err().to_string();
+        assert!(err.contains("`nth` requires a list, not string!"), "{err}");
+        assert!(run_lisp("(nth 1.5 [1 2])", "-").is_err());
+    }
+    #[test]
+    fn test_car_cdr_cons() {
+        assert_eq!(run_lisp("(cons 1 (list 2 3))", "-").unwrap(), "(1 2 3)");
+        assert_eq!(run_lisp("(cons [1] [])", "-").unwrap(), "((1))");
+        assert_eq!(run_lisp("(car (list 1 2))", "-").unwrap(), "1");
+        assert_eq!(run_lisp("(cdr (list 1 2 3))", "-").unwrap(), "(2 3)");
+        assert_eq!(run_lisp("(cdr [1])", "-").unwrap(), "()");
+        assert_eq!(run_lisp("(car $ cdr $ cons 0 [1 2])", "-").unwrap(), "1");
+        for source in ["(car [])", "(cdr [])"] {
+            let err = run_lisp(source, "-").unwrap_err().to_string();
+            assert!(err.contains("was given a list that is too short!"), "{err}");
+        }
+        let err = run_lisp("(cons 1 2)", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("`cons` requires a list, not integer!"),
+            "{err}"
+        );
+    }
+    #[test]
+    fn test_empty_parens_are_an_empty_list() {
+        assert_eq!(*run_in_var("()").get(), LispType::List(Vec::new()));
+        assert_eq!(run_lisp("(len ())", "-").unwrap(), "0");
+        assert_eq!(run_lisp("(cons 1 ())", "-").unwrap(), "(1)");
+        assert_eq!(run_lisp("(deep-equal ( ) [])", "-").unwrap(), "true");
+    }
+    #[test]
+    fn test_unbalanced_input_is_an_error() {
+        let err = run_lisp("", "-").unwrap_err();
+        assert!(matches!(
+            err.parse_errors().next(),
+            Some(ParseError::EmptyStatement { .. })
+        ));
+        let start = start_of("-");
+        assert!(make_ast(&[], &mut Scope::default(), &start).is_err());
+        for (source, paren) in [("(", '('), (")", ')'), ("(+ 1 2", '('), ("(()", '(')] {
+            let err = run_lisp(source, "-").unwrap_err();
+            match err.parse_errors().next() {
+                Some(ParseError::UnmatchedParen { paren: p, .. }) => assert_eq!(*p, paren),
+                other => panic!("{source:?} gave {other:?}"),
+            };
+        }
+    }
+    #[test]
+    fn test_char_literals() {
+        let toks = tokenize("(char-at \"abc\" 1) #\\a #\\space #\\ab", "-".to_string()).unwrap();
+        assert_eq!(toks[5].dat, TokenType::Recognizable(LispType::Char('a')));
+        assert_eq!(toks[6].dat, TokenType::Recognizable(LispType::Char(' ')));
+        assert_eq!(toks[7].dat, TokenType::Ident("#\\ab".to_string()));
+        assert_eq!(LispType::Char('a').to_string(), "a");
+        assert_eq!(LispType::from('z'), LispType::Char('z'));
+        assert_eq!(
+            *run_in_var("(char-at \"héllo\" 1)").get(),
+            LispType::Char('é')
+        );
+        assert_eq!(
+            run_lisp("(deep-equal #\\b (char-at \"abc\" 1))", "-").unwrap(),
+            "true"
+        );
+        assert_eq!(run_lisp("(list #\\x #\\y)", "-").unwrap(), "(x y)");
+        // Characters that would otherwise end a token can still be written after `#\\`.
+        assert_eq!(
+            run_lisp("(list #\\( #\\) #\\[ #\\] #\\; #\\\" #\\$ #\\')", "-").unwrap(),
+            "(( ) [ ] ; \" $ ')"
+        );
+        let toks = tokenize("(list #\\))", "-".to_string()).unwrap();
+        assert_eq!(toks[2].dat, TokenType::Recognizable(LispType::Char(')')));
+        assert_eq!(toks.len(), 4);
+        let err = run_lisp("(char-at \"abc\" 3)", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Index 3 is out of range for a string of length 3!"),
+            "{err}"
+        );
+        assert!(run_lisp("(char-at \"abc\" -1)", "-").is_err());
+    }
+    #[test]
+    fn test_do() {
+        assert_eq!(run_lisp("(do (print 1) (+ 2 3))", "-").unwrap(), "5");
+        assert_eq!(run_lisp("(do)", "-").unwrap(), "nil");
+        assert_eq!(
+            run_lisp("(do (let x 1) (set x (+ x 1)) (set x (* x 10)) x)", "-").unwrap(),
+            "20"
+        );
+        // Every argument is run, even when its value isn't used.
+        let ticks = Rc::new(Cell::new(0));
+        let mut scope = Scope::default();
+        scope.register("tick", Tick(Rc::clone(&ticks)));
+        assert_eq!(
+            run_in_scope("(do (tick) (tick) \"done\")", &mut scope),
+            Ok("done".to_string())
+        );
+        assert_eq!(ticks.get(), 2);
+    }
+    #[test]
+    fn test_min_max() {
+        assert_eq!(run_lisp("(max 3 7 2)", "-").unwrap(), "7");
+        assert_eq!(run_lisp("(min 3 7 2)", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(max 1.5 -2.5)", "-").unwrap(), "1.5");
+        assert_eq!(run_lisp("(min 1.5 -2.5)", "-").unwrap(), "-2.5");
+        // Integers are turned into floats if any argument is a float.
+        assert_eq!(run_lisp("(max 1 2.5 3)", "-").unwrap(), "3.0");
+        assert_eq!(run_lisp("(max 4)", "-").unwrap(), "4");
+        assert_eq!(run_lisp("(min 4.5)", "-").unwrap(), "4.5");
+        let err = run_lisp("(max)", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("This function takes at least 1 argument, but 0 were given!"),
+            "{err}"
+        );
+        assert!(run_lisp("(min 1 \"2\")", "-").is_err());
+    }
+    #[test]
+    fn test_integer_overflow() {
+        let err = run_lisp("(* 9223372036854775807 2)", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Overflow when working out 9223372036854775807 * 2!"),
+            "{err}"
+        );
+        let err = run_lisp("(+ 1 9223372036854775807)", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Overflow"), "{err}");
+        let err = run_lisp("(- -9223372036854775807 2)", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Overflow"), "{err}");
+        // Floats don't overflow, and small integers are fine.
+        assert!(run_lisp("(* 9223372036854775807 2.0)", "-").is_ok());
+        assert_eq!(run_lisp("(+ 1 2 3)", "-").unwrap(), "6");
+    }
+    #[test]
+    fn test_digit_separators() {
+        let toks = tokenize("(+ 1_000_000 1_000.5 -2_5 _5 5_ 1__0 a_b)", "-".to_string()).unwrap();
+        assert_eq!(
+            toks[2].dat,
+            TokenType::Recognizable(LispType::Integer(1_000_000))
+        );
+        assert_eq!(
+            toks[3].dat,
+            TokenType::Recognizable(LispType::Floating(1000.5))
+        );
+        assert_eq!(toks[4].dat, TokenType::Recognizable(LispType::Integer(-25)));
+        for (i, name) in ["_5", "5_", "1__0", "a_b"].into_iter().enumerate() {
+            assert_eq!(toks[5 + i].dat, TokenType::Ident(name.to_string()));
+        }
+        assert_eq!(run_lisp("(+ 1_000 1)", "-").unwrap(), "1001");
+        assert!(run_lisp("(+ _5 1)", "-").is_err());
+    }
+    #[test]
+    fn test_type_of() {
+        for (source, name) in [
+            ("(type-of 1)", "integer"),
+            ("(type-of 1.5)", "float"),
+            ("(type-of \"a\")", "string"),
+            ("(type-of #\\a)", "char"),
+            ("(type-of nil)", "nil"),
+            ("(type-of (= 1 1))", "bool"),
+            ("(type-of +)", "function"),
+            ("(type-of (fn (x) x))", "function"),
+            ("(type-of [1 2])", "list"),
+            ("(type-of (quote a))", "symbol"),
+            ("(type-of (lazy 1))", "promise"),
+            ("(type-of (+ 1 2))", "integer"),
+        ] {
+            assert_eq!(run_lisp(source, "-").unwrap(), name, "{source}");
+        }
+    }
+    #[test]
+    fn test_columns_next_to_parens() {
+        let columns = |source: &str| -> Vec<(usize, TokenType)> {
+            tokenize(source, "-".to_string())
+                .unwrap()
+                .into_iter()
+                .map(|t| (t.loc.col, t.dat))
+                .collect()
+        };
+        let a = || TokenType::Ident("a".to_string());
+        assert_eq!(
+            columns("(a)"),
+            vec![(0, TokenType::StartStmt), (1, a()), (2, TokenType::EndStmt)]
+        );
+        assert_eq!(
+            columns("( a )"),
+            vec![(0, TokenType::StartStmt), (2, a()), (4, TokenType::EndStmt)]
+        );
+        assert_eq!(columns("a)"), vec![(0, a()), (1, TokenType::EndStmt)]);
+        assert_eq!(
+            columns("(abc\"d\")"),
+            vec![
+                (0, TokenType::StartStmt),
+                (1, TokenType::Ident("abc".to_string())),
+                (4, TokenType::Recognizable(LispType::Str("d".to_string()))),
+                (7, TokenType::EndStmt)
+            ]
+        );
+    }
+    #[test]
+    fn test_map() {
+        assert_eq!(
+            run_lisp("(do (fn square (x) (* x x)) (map square [1 2 3]))", "-").unwrap(),
+            "(1 4 9)"
+        );
+        assert_eq!(run_lisp("(map car [[1 2] [3]])", "-").unwrap(), "(1 3)");
+        assert_eq!(run_lisp("(map (fn (x) x) [])", "-").unwrap(), "()");
+        let err = run_lisp("(map (fn (x) (* x x)) (list 1 \"a\" 3))", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("While mapping item 1 of the list."), "{err}");
+        assert!(run_lisp("(map 1 [1])", "-").is_err());
+        assert!(run_lisp("(map car 1)", "-").is_err());
+    }
+    #[test]
+    fn test_filter() {
+        assert_eq!(
+            run_lisp(
+                "(do (fn even? (x) (= (modulo x 2) 0)) (filter even? [1 2 3 4]))",
+                "-"
+            )
+            .unwrap(),
+            "(2 4)"
+        );
+        // `nil` and `0` are false too.
+        assert_eq!(
+            run_lisp("(filter (fn (x) (modulo x 3)) [1 2 3 4 5 6])", "-").unwrap(),
+            "(1 2 4 5)"
+        );
+        assert_eq!(
+            run_lisp("(filter car [[1] [nil] [2]])", "-").unwrap(),
+            "((1) (2))"
+        );
+        let err = run_lisp("(filter (fn (x) (modulo x 2)) [1 \"a\"])", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("While filtering item 1 of the list."), "{err}");
+    }
+    #[test]
+    fn test_mixed_comparisons() {
+        assert_eq!(run_lisp("(< 1 1.5)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(< 1.5 1)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(> 2 1.5)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(> 1.5 2)", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(<= 2 2.0)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(>= 2.0 2)", "-").unwrap(), "true");
+        assert_eq!(LispType::Integer(3).as_f64(), Some(3.0));
+        assert_eq!(LispType::Floating(0.5).as_f64(), Some(0.5));
+        assert_eq!(LispType::Str("1".to_string()).as_f64(), None);
+        let err = run_lisp("(< 1 \"2\")", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("Incompatible types for comparison: number and string"),
+            "{err}"
+        );
+    }
+    #[test]
+    fn test_wrong_arity_is_reported_uniformly() {
+        for (source, expected) in [
+            (
+                "(print 1 2)",
+                "This function takes exactly 1 argument, but 2 were given!",
+            ),
+            (
+                "(+ 1)",
+                "This function takes at least 2 arguments, but 1 were given!",
+            ),
+            (
+                "(if 1)",
+                "This function takes between 2 and 3 arguments, but 1 were given!",
+            ),
+            (
+                "(time-now 5)",
+                "This function takes exactly 0 arguments, but 1 were given!",
+            ),
+            (
+                "(do (fn f (a b) a) (f 1))",
+                "This function takes exactly 2 arguments, but 1 were given!",
+            ),
+        ] {
+            let err = run_lisp(source, "-").unwrap_err().to_string();
+            assert!(err.contains(expected), "{source}: {err}");
+        }
+    }
+    #[test]
+    fn test_too_few_arithmetic_arguments() {
+        assert!(run_lisp("(+ 5)", "-").is_err());
+        assert!(run_lisp("(* 3)", "-").is_err());
+        // Calls that don't go through a statement are checked too.
+        let err = run_lisp("(spread + [5])", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("`+` requires at least 2 arguments, but 1 were given!"),
+            "{err}"
+        );
+        let loc = start_of("-");
+        for op in [
+            IntrinsicOp::Add,
+            IntrinsicOp::Subtract,
+            IntrinsicOp::Multiply,
+            IntrinsicOp::Divide,
+        ] {
+            assert!(op.call(&[Var::new(3isize)], &loc).is_err(), "{op:?}");
+        }
+    }
+    #[test]
+    fn test_bool_literals() {
+        let toks = tokenize("(and true false True)", "-".to_string()).unwrap();
+        assert_eq!(toks[2].dat, TokenType::Recognizable(LispType::Bool(true)));
+        assert_eq!(toks[3].dat, TokenType::Recognizable(LispType::Bool(false)));
+        assert_eq!(toks[4].dat, TokenType::Ident("True".to_string()));
+        assert_eq!(run_lisp("(list true false)", "-").unwrap(), "(true false)");
+        assert_eq!(run_lisp("(= 1 1)", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(deep-equal true (= 1 1))", "-").unwrap(), "true");
+        assert_eq!(run_lisp("(if false 1 2)", "-").unwrap(), "2");
+        assert!(!LispType::Nil.is_truthy());
+        assert!(!LispType::Integer(0).is_truthy());
+        assert!(!LispType::Bool(false).is_truthy());
+        assert!(LispType::Str(String::new()).is_truthy());
+        assert!(LispType::Bool(true).is_truthy());
+        assert_eq!(run_lisp("(if \"\" 1 2)", "-").unwrap(), "1");
+        assert_eq!(run_lisp("(if nil 1 2)", "-").unwrap(), "2");
+        assert_eq!(run_lisp("(if 0 1 2)", "-").unwrap(), "2");
+    }
+    #[test]
+    fn test_cond() {
+        let mut scope = Scope::default();
+        scope.register("boom", Boom);
+        // Only the tests up to the one that matches and its value are run.
+        assert_eq!(
+            run_in_scope(
+                "(cond ((= 1 2) (boom)) ((< 1 2) \"middle\") ((boom) (boom)))",
+                &mut scope
+            ),
+            Ok("middle".to_string())
+        );
+        assert_eq!(
+            run_lisp("(cond (false 1) (nil 2) (else (+ 1 2)))", "-").unwrap(),
+            "3"
+        );
+        assert_eq!(run_lisp("(cond (false 1))", "-").unwrap(), "nil");
+        assert_eq!(run_lisp("(+ 1 (cond (true 2)))", "-").unwrap(), "3");
+        let err = run_lisp("(cond (1))", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("A `cond` clause needs a test and a value!"),
+            "{err}"
+        );
+        let err = run_lisp("(cond 1 2)", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("Each `cond` clause must be in parentheses!"),
+            "{err}"
+        );
+    }
+    #[test]
+    fn test_list_display() {
+        let list = LispType::List(vec![Var::new(1isize), Var::new(2isize), Var::new(3isize)]);
+        assert_eq!(list.to_string(), "(1 2 3)");
+        assert_eq!(LispType::List(Vec::new()).to_string(), "()");
+        assert_eq!(
+            run_lisp("(list 1 [2 []] \"three\")", "-").unwrap(),
+            "(1 (2 ()) three)"
+        );
+    }
+    #[test]
+    fn test_closures() {
+        let loc = start_of("-");
+        let call = |f: &Var, args: &[Var]| f.get().unwrap_func().call(args, &loc).unwrap();
+        // Each counter keeps its own `n`, even after `make-counter` has returned.
+        let counters = run_in_var(
+            "(do (fn make-counter () (do (let n 0) (fn () (set n (+ n 1))))) \
+             (list (make-counter) (make-counter)))",
+        );
+        let counters = match &*counters.get() {
+            LispType::List(items) => items.clone(),
+            other => panic!("Expected a list, got {other}"),
+        };
+        for expected in 1..=3isize {
+            assert_eq!(*call(&counters[0], &[]).get(), LispType::Integer(expected));
+        }
+        assert_eq!(*call(&counters[1], &[]).get(), LispType::Integer(1));
+
+        let add_two = run_in_var("(do (fn adder (x) (fn (y) (+ x y))) (adder 2))");
+        assert_eq!(
+            *call(&add_two, &[Var::new(3isize)]).get(),
+            LispType::Integer(5)
+        );
+        let mut outer = Scope::default();
+        outer.vars.insert("x-outer".to_string(), Var::new(1isize));
+        let mut inner = Scope::child(Rc::new(outer));
+        inner.vars.insert("x-inner".to_string(), Var::new(2isize));
+        assert_eq!(*inner.get("x-outer").unwrap().get(), LispType::Integer(1));
+        assert!(inner.get("+").is_some());
+        assert!(inner.get("x-missing").is_none());
+        assert_eq!(inner.completions("x-"), vec!["x-inner", "x-outer"]);
+        // Parameters are looked up in the function's own scope first.
+        assert_eq!(
+            run_lisp(
+                "(do (fn outer (a) (map (fn (b) (* a b)) [1 2 3])) (outer 10))",
+                "-"
+            )
+            .unwrap(),
+            "(10 20 30)"
+        );
+    }
+    #[test]
+    fn test_tail_calls() {
+        // Without tail calls, this would go far past the maximum depth.
+        assert_eq!(
+            run_lisp(
+                "(do (fn countdown (n) (if (= n 0) \"done\" (countdown (- n 1)))) (countdown 20000))",
+                "-"
+            )
+            .unwrap(),
+            "done"
+        );
+        assert_eq!(
+            run_lisp(
+                "(do (fn sum (n acc) (cond ((= n 0) acc) (else (do (+ 1 1) (sum (- n 1) (+ acc n)))))) \
+                 (sum 5000 0))",
+                "-"
+            )
+            .unwrap(),
+            "12502500"
+        );
+        assert_eq!(
+            run_lisp(
+                "(do (fn count (n) (if (= n 0) 0 (+ 1 (count (- n 1))))) (count 10))",
+                "-"
+            )
+            .unwrap(),
+            "10"
+        );
+    }
+    #[test]
+    fn test_str() {
+        assert_eq!(
+            *run_in_var("(str 42)").get(),
+            LispType::Str("42".to_string())
+        );
+        assert_eq!(
+            *run_in_var("(str 3.14)").get(),
+            LispType::Str("3.14".to_string())
+        );
+        assert_eq!(
+            *run_in_var("(str nil)").get(),
+            LispType::Str("nil".to_string())
+        );
+        assert_eq!(
+            *run_in_var("(str [1 [2]])").get(),
+            LispType::Str("(1 (2))".to_string())
+        );
+        assert_eq!(
+            run_lisp("(concat \"n = \" (str (+ 1 2)))", "-").unwrap(),
+            "n = 3"
+        );
+        assert_eq!(run_lisp("(len (str 12345))", "-").unwrap(), "5");
+    }
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(
+            *run_in_var("(parse-int \"42\")").get(),
+            LispType::Integer(42)
+        );
+        assert_eq!(
+            *run_in_var("(parse-int \"-7\")").get(),
+            LispType::Integer(-7)
+        );
+        assert_eq!(run_lisp("(+ 1 (parse-int (str 41)))", "-").unwrap(), "42");
+        let err = run_lisp("(parse-int \"abc\")", "-")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Cannot read \"abc\" as an integer"), "{err}");
+        assert!(run_lisp("(parse-int \"1.5\")", "-").is_err());
+        let err = run_lisp("(parse-int 42)", "-").unwrap_err().to_string();
+        assert!(
+            err.contains("`parse-int` requires strings, not integer!"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_multiple_top_level_statements() {
+        let source = "(let total 0)
+            (set total (+ total 1))
+            (set total (+ total 10))
+            total";
+        assert_eq!(run_lisp(source, "-").unwrap(), "11");
+        assert_eq!(run_lisp("(fn sq (x) (* x x)) (sq 5)", "-").unwrap(), "25");
+        assert!(check_lisp("(+ 1 2) (+ 3 4)", "-").is_ok());
+        assert!(run_lisp("(+ 1 2) (+ 3", "-").is_err());
+
+        // A `$` at the top level takes in the values before it, instead of starting a statement.
+        let statements = |source: &str| {
+            let toks = tokenize(source, "-".to_string()).unwrap();
+            crate::make_program(&toks, &mut Scope::default(), &toks[0].loc)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(statements("print $ + 1 2"), 1);
+        assert_eq!(statements("(+ 1 2) print $ - 489 $ + 34 35"), 2);
+        assert_eq!(
+            run_lisp("(let total 1) set total $ + total 2", "-").unwrap(),
+            "3"
+        );
+        assert_eq!(run_lisp("list $ + 1 2", "-").unwrap(), "(3)");
+    }
+
+    #[test]
+    fn test_resolve_deep() {
+        let statement = |source: &str| {
+            let toks = tokenize(source, "-".to_string()).unwrap();
+            Var::new(make_ast(&toks, &mut Scope::default(), &toks[0].loc).unwrap())
+        };
+        let inner = Var::new(LispType::List(vec![statement("(* 2 3)")]));
+        let list = Var::new(LispType::List(vec![statement("(+ 1 2)"), inner]));
+        let res = list.resolve_deep().unwrap();
+        assert_eq!(
+            *res.get(),
+            LispType::List(vec![
+                Var::new(3),
+                Var::new(LispType::List(vec![Var::new(6)]))
+            ])
+        );
+        assert!(statement("(+ 1 2)").resolve_deep().unwrap().get().clone() == LispType::Integer(3));
+        let failing = Var::new(LispType::List(vec![statement("(/ 1 0)")]));
+        assert!(failing.resolve_deep().is_err());
+    }
+
+    #[test]
+    fn test_token_ends() {
+        let toks = tokenize("(strlen \"two\nlines\") // done", "-".to_string()).unwrap();
+        let spans: Vec<_> = toks
+            .iter()
+            .map(|t| ((t.loc().line, t.loc().col), (t.end().line, t.end().col)))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ((0, 0), (0, 1)),
+                ((0, 1), (0, 7)),
+                ((0, 8), (1, 6)),
+                ((1, 6), (1, 7))
+            ]
+        );
+        let toks = tokenize("$ + 1 2", "-".to_string()).unwrap();
+        let last = toks.last().unwrap();
+        assert_eq!(last.loc(), last.end());
+    }
+
+    #[test]
+    fn test_gensym() {
+        let first = run_lisp("(gensym)", "-").unwrap();
+        let second = run_lisp("(gensym 1 2)", "-").unwrap();
+        assert_ne!(first, second);
+        assert!(first.starts_with('g'));
+        assert_eq!(run_lisp("(= (gensym) (gensym))", "-").unwrap(), "false");
+        assert_eq!(run_lisp("(type-of (gensym))", "-").unwrap(), "symbol");
+    }
+
+    #[test]
+    fn test_any_whitespace_separates_tokens() {
+        assert_eq!(run_lisp("(+\t1\t2)", "-").unwrap(), "3");
+        // A no-break space is one character wide, even though it is two bytes.
+        let toks = tokenize("(+\u{a0}1\u{3000}22)", "-".to_string()).unwrap();
+        let cols: Vec<_> = toks.iter().map(|t| t.loc().col).collect();
+        assert_eq!(cols, [0, 1, 3, 5, 7]);
+        assert_eq!(run_lisp("(+\u{a0}1\u{3000}22)", "-").unwrap(), "23");
+        assert_eq!(run_lisp("\"a\tb\"", "-").unwrap(), "a\tb");
+    }
+
+    #[test]
+    fn test_abs_and_sqrt() {
+        assert_eq!(*run_in_var("(abs -5)").get(), LispType::Integer(5));
+        assert_eq!(run_lisp("(abs 5)", "-").unwrap(), "5");
+        assert!(matches!(*run_in_var("(abs -2.5)").get(), LispType::Floating(f) if f == 2.5));
+        assert_eq!(run_lisp("(sqrt 9)", "-").unwrap(), "3.0");
+        assert_eq!(run_lisp("(sqrt 2.25)", "-").unwrap(), "1.5");
+        let err = run_lisp("(sqrt -4)", "-").unwrap_err();
+        assert!(err.to_string().contains("square root of a negative"));
+        assert!(run_lisp("(abs \"5\")", "-").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_result_types() {
+        assert_eq!(*run_in_var("(+ 1 2)").get(), LispType::Integer(3));
+        assert!(matches!(*run_in_var("(+ 1.0 2)").get(), LispType::Floating(f) if f == 3.0));
+        assert!(matches!(
+            *run_in_var("(+ 1.5 1.5)").get(),
+            LispType::Floating(_)
+        ));
+        assert!(matches!(
+            *run_in_var("(* 2 1.5)").get(),
+            LispType::Floating(_)
+        ));
+        assert!(matches!(
+            *run_in_var("(- 5.0 2)").get(),
+            LispType::Floating(_)
+        ));
+        assert_eq!(*run_in_var("(/ 6 3)").get(), LispType::Integer(2));
+        assert_eq!(run_lisp("(type-of (+ 1 2))", "-").unwrap(), "integer");
+        assert_eq!(run_lisp("(type-of (+ 1.0 2))", "-").unwrap(), "float");
+    }
+
+    #[test]
+    fn test_define() {
+        assert_eq!(
+            run_lisp("(define (inc x) (+ x 1)) (inc 4)", "-").unwrap(),
+            "5"
+        );
+        assert_eq!(run_lisp("(define (inc x) (+ x 1))", "-").unwrap(), "nil");
+        assert_eq!(
+            run_lisp(
+                "(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 5)",
+                "-"
+            )
+            .unwrap(),
+            "120"
+        );
+        assert!(run_lisp("(define inc (x) (+ x 1))", "-").is_err());
+        assert!(run_lisp("(define (inc x))", "-").is_err());
+    }
+
+    #[test]
+    fn test_function_can_set_its_own_variable() {
+        // The function's variable used to stay borrowed while it ran, so this panicked.
+        assert_eq!(run_lisp("(fn f () (set f 1)) (f) f", "-").unwrap(), "1");
+        assert_eq!(
+            run_lisp("(fn g (x) (do (set g 0) x)) (map g [1 2])", "-").unwrap(),
+            "(1 2)"
+        );
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        let float = |s: &str| match TokenType::from(s) {
+            TokenType::Recognizable(LispType::Floating(f)) => f,
+            other => panic!("{s} should be a float, but it is {other:?}"),
+        };
+        assert_eq!(float("1e3"), 1000.0);
+        assert_eq!(float("1.5e-3"), 0.0015);
+        assert_eq!(float("2E10"), 2e10);
+        for name in ["1e", "e3", "inf", "nan", "infinity"] {
+            assert_eq!(TokenType::from(name), TokenType::Ident(name.to_string()));
+        }
+        assert_eq!(run_lisp("(+ 1e3 1)", "-").unwrap(), "1001.0");
+    }
+
+    #[test]
+    fn test_calling_a_variable_set_to_a_non_function() {
+        for source in ["(fn f (x) x) (set f 5) (f 1)", "(set + 1) (+ 1 2)"] {
+            let err = run_lisp(source, "-").unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("Only functions can be called, not integer!"));
+        }
+        assert!(check_lisp("(fn f (x) x) (set f 5) (f 1)", "-").is_ok());
+    }
+
+    #[test]
+    fn test_letrec() {
+        let bindings = "((even? (fn (n) (if (= n 0) true (odd? (- n 1))))) \
+                         (odd? (fn (n) (if (= n 0) false (even? (- n 1))))))";
+        let run = |body: &str| run_lisp(&format!("(letrec {bindings} {body})"), "-");
+        assert_eq!(run("even? 10").unwrap(), "true");
+        assert_eq!(run("odd? 10").unwrap(), "false");
+        // A plain `let` can't do this, because each function only sees what was bound before it.
+        assert!(run_lisp(
+            "(do (fn is-even (n) (if (= n 0) true (is-odd (- n 1)))) \
+                 (fn is-odd (n) (if (= n 0) false (is-even (- n 1)))) \
+                 (is-even 2))",
+            "-"
+        )
+        .is_err());
+        assert!(run_lisp("(letrec ((x 1)) x)", "-").is_err());
+        assert!(run_lisp("(letrec x 1)", "-").is_err());
+    }
+
+    #[test]
+    fn test_case_lambda() {
+        let add = "(case-lambda add (() 0) ((x) (add x 1)) ((x y) (+ x y)))";
+        let run = |call: &str| run_lisp(&format!("{add} {call}"), "-");
+        assert_eq!(run("(add)").unwrap(), "0");
+        assert_eq!(run("(add 5)").unwrap(), "6");
+        assert_eq!(run("(add 2 3)").unwrap(), "5");
+        assert!(run("(add 1 2 3)").is_err());
+        // Gaps between the cases are only found when it is called.
+        let err = run_lisp("(case-lambda f ((x) x) ((x y z) x)) (f 1 2)", "-").unwrap_err();
+        assert!(err.to_string().contains("No case"));
+        assert!(run_lisp("(case-lambda)", "-").is_err());
+        assert!(run_lisp("(case-lambda f (x x))", "-").is_err());
+    }
+
+    #[test]
+    fn test_with_redefs() {
+        let greet = "(fn greet () \"hello\") (fn greet-twice () (concat (greet) (greet)))";
+        let run = |rest: &str| run_lisp(&format!("{greet} {rest}"), "-");
+        assert_eq!(
+            run("(with-redefs ((greet (fn () \"hi\"))) (greet-twice))").unwrap(),
+            "hihi"
+        );
+        assert_eq!(
+            run("(with-redefs ((greet (fn () \"hi\"))) (greet)) (greet-twice)").unwrap(),
+            "hellohello"
+        );
+        assert_eq!(
+            run_lisp(
+                "(let x 1) (fn get-x () x) (with-redefs ((x 5)) (get-x))",
+                "-"
+            )
+            .unwrap(),
+            "5"
+        );
+
+        // The old values come back even when the body fails.
+        let mut scope = Scope::default();
+        run_in_scope("(let x 1)", &mut scope).unwrap();
+        assert!(run_in_scope("(with-redefs ((x 5)) / x 0)", &mut scope).is_err());
+        assert_eq!(run_in_scope("x", &mut scope).unwrap(), "1");
+
+        assert!(run_lisp("(with-redefs ((nope 1)) 1)", "-").is_err());
+        assert!(run_lisp("(let x 1) (with-redefs ((x)) x)", "-").is_err());
+        assert!(run_lisp("(let x 1) (with-redefs ((x 2)))", "-").is_err());
+    }
+
+    #[test]
+    fn test_run_lisp_all_keeps_going() {
+        let summary = crate::run_lisp_all("(let x 1) (/ x 0) (set x 5)", "-").unwrap();
+        assert_eq!((summary.succeeded(), summary.failed()), (2, 1));
+        assert!(summary.results[1].is_err());
+        assert_eq!(summary.results[2].as_ref().unwrap().to_string(), "5");
+        assert!(summary.errors().to_string().contains("Division by zero!"));
+        // `run_lisp` stops at the first error instead.
+        assert!(run_lisp("(let x 1) (/ x 0) (set x 5)", "-").is_err());
+        // Parse errors mean nothing is run at all.
+        assert!(crate::run_lisp_all("(+ 1 2) (nope)", "-").is_err());
     }
 }
@@ -8,9 +8,21 @@ use crate::types::LispType;
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub(crate) loc: Location,
+    // Just past the last character of the token, so `end.col - loc.col` is its length when it is
+    // all on one line.
+    pub(crate) end: Location,
     pub(crate) dat: TokenType,
 }
 
+impl Token {
+    pub fn loc(&self) -> &Location {
+        &self.loc
+    }
+    pub fn end(&self) -> &Location {
+        &self.end
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Location {
     pub filename: String,
@@ -26,12 +38,24 @@ impl Display for Location {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) enum KeyWord {
     Let,
+    LetRec,
+    DebugScope,
+    If,
+    Fn,
+    CaseLambda,
+    Define,
+    Set,
+    Quote,
+    Cond,
+    WithRedefs,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum TokenType {
     StartStmt,
     EndStmt,
+    OpenBracket,
+    CloseBracket,
     KeyWord(KeyWord),
     Recognizable(LispType),
     Ident(String),
@@ -42,28 +66,100 @@ impl FromStr for KeyWord {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.trim().to_ascii_lowercase().as_str() {
             "let" => Ok(Self::Let),
+            "letrec" => Ok(Self::LetRec),
+            "debug-scope" => Ok(Self::DebugScope),
+            "if" => Ok(Self::If),
+            "fn" | "lambda" => Ok(Self::Fn),
+            "case-lambda" => Ok(Self::CaseLambda),
+            "define" => Ok(Self::Define),
+            "set" => Ok(Self::Set),
+            "quote" => Ok(Self::Quote),
+            "cond" => Ok(Self::Cond),
+            "with-redefs" => Ok(Self::WithRedefs),
             _ => Err("Unknown keyword!"),
         }
     }
 }
 
+impl Display for KeyWord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            KeyWord::Let => "let",
+            KeyWord::LetRec => "letrec",
+            KeyWord::DebugScope => "debug-scope",
+            KeyWord::If => "if",
+            KeyWord::Fn => "fn",
+            KeyWord::CaseLambda => "case-lambda",
+            KeyWord::Define => "define",
+            KeyWord::Set => "set",
+            KeyWord::Quote => "quote",
+            KeyWord::Cond => "cond",
+            KeyWord::WithRedefs => "with-redefs",
+        };
+        write!(f, "{name}")
+    }
+}
+
 impl TokenType {
     fn new_str_lit(source: String) -> Self {
         Self::Recognizable(LispType::Str(source))
     }
 }
 
+// `#\a` is the character `a`. Any character can come after the `#\`, including parentheses,
+// brackets, `;` and `"`, like `#\(`. Whitespace still ends a token, so it is written `#\space`,
+// `#\tab` and `#\newline` instead.
+fn char_literal(s: &str) -> Option<char> {
+    let name = s.strip_prefix("#\\")?;
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => match name {
+            "space" => Some(' '),
+            "newline" => Some('\n'),
+            "tab" => Some('\t'),
+            _ => None,
+        },
+    }
+}
+
+// Numbers can have underscores between digits to make them easier to read, like `1_000_000`. This
+// takes them out, or gives back `None` if there is an underscore that isn't between two digits.
+fn strip_digit_separators(s: &str) -> Option<String> {
+    let chars: Vec<char> = s.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' {
+            let before = i.checked_sub(1).map(|i| chars[i]);
+            let after = chars.get(i + 1).copied();
+            if !before.is_some_and(|c| c.is_ascii_digit())
+                || !after.is_some_and(|c| c.is_ascii_digit())
+            {
+                return None;
+            }
+        }
+    }
+    Some(s.replace('_', ""))
+}
+
 impl<T: ToString> From<T> for TokenType {
     fn from(orig: T) -> Self {
         let s = orig.to_string().trim().to_string();
+        let number = strip_digit_separators(&s).unwrap_or_default();
+        // Floats can be written in scientific notation, like `1.5e-3`, but they need a digit, so that
+        // names like `inf` and `nan` aren't read as floats. `1e` is an identifier.
+        let has_digit = number.contains(|c: char| c.is_ascii_digit());
         if let Ok(k) = s.parse::<KeyWord>() {
             Self::KeyWord(k)
-        } else if let Ok(i) = s.parse::<isize>() {
+        } else if let Ok(i) = number.parse::<isize>() {
             Self::Recognizable(i.into())
-        } else if let Ok(f) = s.parse::<f64>() {
+        } else if let Some(f) = number.parse::<f64>().ok().filter(|_| has_digit) {
             Self::Recognizable(f.into())
         } else if &s == "nil" {
             Self::Recognizable(LispType::Nil)
+        } else if let Ok(b) = s.parse::<bool>() {
+            Self::Recognizable(b.into())
+        } else if let Some(c) = char_literal(&s) {
+            Self::Recognizable(c.into())
         } else {
             Self::Ident(orig.to_string())
         }
@@ -81,8 +177,11 @@ enum TokenizerStatus {
 struct Tokenizer<'a> {
     tokens: Vec<Token>,
     right_assocs: usize,
+    // The column and line of the character being looked at.
     pos: (usize, usize),
-    pos_locked: bool,
+    // Where the token in `token_buf` started, and where it ends.
+    token_start: (usize, usize),
+    token_end: (usize, usize),
     token_buf: String,
     status: TokenizerStatus,
     default_buf_len: usize,
@@ -98,7 +197,8 @@ impl<'a> Tokenizer<'a> {
         Tokenizer {
             tokens: Vec::with_capacity(default_buf_len),
             pos: (0, 0),
-            pos_locked: false,
+            token_start: (0, 0),
+            token_end: (0, 0),
             token_buf: String::with_capacity(default_buf_len),
             status: TokenizerStatus::Normal,
             default_buf_len,
@@ -109,16 +209,26 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    fn loc_at(&self, (col, line): (usize, usize)) -> Location {
+        Location {
+            filename: self.filename.clone(),
+            line,
+            col,
+        }
+    }
+
+    // Where a token that is only the character being looked at ends.
+    fn after_pos(&self) -> (usize, usize) {
+        (self.pos.0 + 1, self.pos.1)
+    }
+
     fn push_tok(&mut self) {
         match self.status {
             TokenizerStatus::Normal => {
                 if self.token_buf.trim() != "" {
                     let tok = Token {
-                        loc: Location {
-                            line: self.pos.1,
-                            col: self.pos.0,
-                            filename: self.filename.clone(),
-                        },
+                        loc: self.loc_at(self.token_start),
+                        end: self.loc_at(self.token_end),
                         dat: mem::replace(
                             &mut self.token_buf,
                             String::with_capacity(self.default_buf_len),
@@ -126,24 +236,19 @@ impl<'a> Tokenizer<'a> {
                         .into(),
                     };
                     self.tokens.push(tok);
-                    self.pos_locked = false;
                 }
             }
             TokenizerStatus::Comment => unreachable!(),
             TokenizerStatus::String => {
                 let tok = Token {
-                    loc: Location {
-                        line: self.pos.1,
-                        col: self.pos.0,
-                        filename: self.filename.clone(),
-                    },
+                    loc: self.loc_at(self.token_start),
+                    end: self.loc_at(self.token_end),
                     dat: TokenType::new_str_lit(mem::replace(
                         &mut self.token_buf,
                         String::with_capacity(self.default_buf_len),
                     )),
                 };
                 self.tokens.push(tok);
-                self.pos_locked = false;
                 self.status = TokenizerStatus::Normal;
             }
         }
@@ -151,25 +256,30 @@ impl<'a> Tokenizer<'a> {
 
     fn start_stmt(&mut self) {
         let tok = Token {
-            loc: Location {
-                filename: self.filename.clone(),
-                line: self.pos.1,
-                col: self.pos.0,
-            },
+            loc: self.loc_at(self.pos),
+            end: self.loc_at(self.after_pos()),
             dat: TokenType::StartStmt,
         };
         self.tokens.push(tok);
     }
 
+    // Brackets don't interact with `$`, so they only need to end the current token.
+    fn bracket(&mut self, dat: TokenType) {
+        self.push_tok();
+        let tok = Token {
+            loc: self.loc_at(self.pos),
+            end: self.loc_at(self.after_pos()),
+            dat,
+        };
+        self.tokens.push(tok);
+    }
+
     fn end_stmt(&mut self) {
         self.token_buf = self.token_buf.trim().to_string();
         if !self.token_buf.is_empty() {
             let tok = Token {
-                loc: Location {
-                    filename: self.filename.clone(),
-                    line: self.pos.1,
-                    col: self.pos.0,
-                },
+                loc: self.loc_at(self.token_start),
+                end: self.loc_at(self.token_end),
                 dat: mem::replace(
                     &mut self.token_buf,
                     String::with_capacity(self.default_buf_len),
@@ -179,65 +289,98 @@ impl<'a> Tokenizer<'a> {
             self.token_buf = String::with_capacity(self.default_buf_len);
             self.tokens.push(tok);
         }
+        // The statements started by `$` aren't closed by anything in the source, so they end
+        // where they are closed without taking up any space.
         for _ in 0..self.right_assocs {
             let tok = Token {
-                loc: Location {
-                    filename: self.filename.clone(),
-                    line: self.pos.1,
-                    col: self.pos.0,
-                },
+                loc: self.loc_at(self.pos),
+                end: self.loc_at(self.pos),
                 dat: TokenType::EndStmt,
             };
             self.tokens.push(tok);
         }
         self.right_assocs = 0;
-        self.pos_locked = false;
         self.status = TokenizerStatus::Normal;
         let tok = Token {
-            loc: Location {
-                filename: self.filename.clone(),
-                line: self.pos.1,
-                col: self.pos.0,
-            },
+            loc: self.loc_at(self.pos),
+            end: self.loc_at(self.after_pos()),
             dat: TokenType::EndStmt,
         };
         self.tokens.push(tok);
     }
 
     fn tokenize(mut self) -> Result<Vec<Token>, LispErrors> {
-        'lines: for (line_number, line_data) in self.source.lines().enumerate() {
-            for (col_number, character) in line_data.trim().char_indices() {
+        for (line_number, line_data) in self.source.lines().enumerate() {
+            // Columns count characters in the line as it is written, including any indentation.
+            for (col_number, character) in line_data.chars().enumerate() {
+                self.pos = (col_number, line_number);
                 match (character, self.status, self.last_character) {
-                    ('\"', TokenizerStatus::String, _) => self.push_tok(),
+                    ('\"', TokenizerStatus::String, _) => {
+                        self.token_end = self.after_pos();
+                        self.push_tok();
+                    }
                     (_, TokenizerStatus::String, _) => self.token_buf.push(character),
-                    ('\"', TokenizerStatus::Normal, _) => self.status = TokenizerStatus::String,
-                    (' ', TokenizerStatus::Normal, _) => self.push_tok(),
+                    // The character after `#\` is part of the literal, even if it would
+                    // otherwise end the token, so that `#\(` and `#\;` can be written.
+                    (c, TokenizerStatus::Normal, _)
+                        if self.token_buf == "#\\" && !c.is_whitespace() =>
+                    {
+                        self.token_end = self.after_pos();
+                        self.token_buf.push(character);
+                    }
+                    ('\"', TokenizerStatus::Normal, _) => {
+                        self.push_tok();
+                        self.token_start = self.pos;
+                        self.status = TokenizerStatus::String;
+                    }
+                    (c, TokenizerStatus::Normal, _) if c.is_whitespace() => self.push_tok(),
                     ('(', TokenizerStatus::Normal, _) => self.start_stmt(),
                     (')', TokenizerStatus::Normal, _) => self.end_stmt(),
-                    ('/', TokenizerStatus::Normal, '/') => continue 'lines,
+                    ('[', TokenizerStatus::Normal, _) => self.bracket(TokenType::OpenBracket),
+                    (']', TokenizerStatus::Normal, _) => self.bracket(TokenType::CloseBracket),
+                    // The first `/` was already put in the buffer.
+                    ('/', TokenizerStatus::Normal, '/') => {
+                        self.token_buf.pop();
+                        self.token_end = (self.pos.0 - 1, self.pos.1);
+                        break;
+                    }
+                    (';', TokenizerStatus::Normal, _) => break,
+                    // A `'` at the start of a token quotes whatever comes after it. Anywhere else
+                    // it's part of the name, like in `don't`.
+                    ('\'', TokenizerStatus::Normal, _) if self.token_buf.is_empty() => {
+                        self.token_start = self.pos;
+                        self.token_end = self.after_pos();
+                        self.token_buf.push(character);
+                        self.push_tok();
+                    }
                     ('$', TokenizerStatus::Normal, _) => {
                         self.start_stmt();
                         self.right_assocs += 1;
                     }
                     ('*', TokenizerStatus::Normal, '{') => self.status = TokenizerStatus::Comment,
-                    (_, TokenizerStatus::Normal, _) => self.token_buf.push(character),
+                    (_, TokenizerStatus::Normal, _) => {
+                        if self.token_buf.is_empty() {
+                            self.token_start = self.pos;
+                        }
+                        self.token_end = self.after_pos();
+                        self.token_buf.push(character);
+                    }
                     ('}', TokenizerStatus::Comment, '*') => self.status = TokenizerStatus::Normal,
                     (_, TokenizerStatus::Comment, _) => {}
                 }
                 self.last_character = character;
-                if !self.pos_locked {
-                    self.pos = (col_number, line_number);
-                }
             }
+            // A token can't continue onto the next line.
+            if let TokenizerStatus::Normal = self.status {
+                self.push_tok();
+            }
+            self.last_character = '\n';
         }
 
         for _ in 0..self.right_assocs {
             let tok = Token {
-                loc: Location {
-                    filename: self.filename.clone(),
-                    line: self.pos.1,
-                    col: self.pos.0,
-                },
+                loc: self.loc_at(self.pos),
+                end: self.loc_at(self.pos),
                 dat: TokenType::EndStmt,
             };
             self.tokens.push(tok);
@@ -246,7 +389,66 @@ impl<'a> Tokenizer<'a> {
     }
 }
 
+// `'x` is short for `(quote x)`, and `'(a b)` for `(quote (a b))`. The tokenizer gives each `'` its
+// own token, so this replaces it with the statement that it stands for.
+fn expand_quotes(tokens: Vec<Token>) -> Result<Vec<Token>, LispErrors> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut depth = 0;
+    // The depths that a quote was opened at, and where its `'` was, so that each one can be closed
+    // once the thing after it is complete.
+    let mut pending: Vec<(usize, Location)> = Vec::new();
+    let close = |out: &mut Vec<Token>, depth: &mut usize, pending: &mut Vec<(usize, Location)>| {
+        while let Some((_, loc)) = pending.pop_if(|(d, _)| d == depth) {
+            let end = out
+                .last()
+                .map_or_else(|| loc.clone(), |t: &Token| t.end.clone());
+            out.push(Token {
+                loc: end.clone(),
+                end,
+                dat: TokenType::EndStmt,
+            });
+            *depth -= 1;
+        }
+    };
+    for tok in tokens {
+        match &tok.dat {
+            TokenType::Ident(id) if id == "'" => {
+                out.push(Token {
+                    dat: TokenType::StartStmt,
+                    ..tok.clone()
+                });
+                out.push(Token {
+                    dat: TokenType::KeyWord(KeyWord::Quote),
+                    ..tok.clone()
+                });
+                depth += 1;
+                pending.push((depth, tok.loc));
+            }
+            TokenType::StartStmt | TokenType::OpenBracket => {
+                depth += 1;
+                out.push(tok);
+            }
+            TokenType::EndStmt | TokenType::CloseBracket => {
+                if let Some((_, loc)) = pending.last().filter(|(d, _)| *d == depth) {
+                    return Err(LispErrors::new().error(loc, "Nothing to quote after `'`!"));
+                }
+                depth = depth.saturating_sub(1);
+                out.push(tok);
+                close(&mut out, &mut depth, &mut pending);
+            }
+            _ => {
+                out.push(tok);
+                close(&mut out, &mut depth, &mut pending);
+            }
+        }
+    }
+    if let Some((_, loc)) = pending.first() {
+        return Err(LispErrors::new().error(loc, "Nothing to quote after `'`!"));
+    }
+    Ok(out)
+}
+
 pub fn tokenize(source: &str, filename: String) -> Result<Vec<Token>, LispErrors> {
     let tokenizer = Tokenizer::new(source, filename);
-    tokenizer.tokenize()
+    expand_quotes(tokenizer.tokenize()?)
 }
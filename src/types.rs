@@ -1,55 +1,138 @@
-use crate::ast::{Statement, Var};
+use crate::ast::{Promise, SeenPairs, Statement, Var};
 use crate::callable::Callable;
+use std::cell::RefCell;
 use std::fmt::Display;
+use std::rc::Rc;
 
 #[derive(Debug)]
-pub(crate) enum LispType {
+pub enum LispType {
     Integer(isize),
     Str(String),
-    Func(Box<dyn Callable>),
+    // Shared so that a function can be called without keeping its variable borrowed.
+    Func(Rc<dyn Callable>),
     Statement(Statement),
     #[allow(dead_code)]
     List(Vec<Var>),
     Floating(f64),
+    Bool(bool),
+    Char(char),
+    Promise(Promise),
+    // A name that isn't looked up, from `quote`.
+    Symbol(String),
     Nil,
     // TODO(#2): Add custom newtypes.
 }
 
+// Functions are copied, but statements, lists and promises share the values inside them.
 impl Clone for LispType {
     fn clone(&self) -> Self {
         match self {
-            Self::Integer(item) => Self::Integer(item.clone()),
+            Self::Integer(item) => Self::Integer(*item),
             Self::Str(item) => Self::Str(item.clone()),
-            Self::Func(_) => panic!("Tried to clone a function! If you see this, this is an internal error and you should report it at <https://github.com/FeistyKit/pale/issues/new>!"),
-            Self::Statement(_) => panic!("Tried to clone a statement! If you see this, this is an internal error and you should report it at <https://github.com/FeistyKit/pale/issues/new>!"),
-            Self::List(_) => panic!("Tried to clone a list! If you see this, this is an internal error and you should report it at <https://github.com/FeistyKit/pale/issues/new>!"),
-            Self::Floating(item) => Self::Floating(item.clone()),
+            Self::Func(f) => Self::Func(Rc::from(f.clone_box())),
+            Self::Statement(s) => Self::Statement(s.clone()),
+            Self::List(items) => Self::List(items.clone()),
+            Self::Floating(item) => Self::Floating(*item),
+            Self::Bool(item) => Self::Bool(*item),
+            Self::Char(item) => Self::Char(*item),
+            Self::Promise(p) => Self::Promise(p.clone()),
+            Self::Symbol(name) => Self::Symbol(name.clone()),
             Self::Nil => Self::Nil,
         }
     }
 }
 
-const FLOATING_EQ_RANGE: f64 = 0.001; // If two floats are less than this far apart, they are considered equal
+// How far apart two numbers can be for `deep-equal` to still count them as equal, so that rounding
+// errors like `(+ 0.1 0.2)` not being exactly `0.3` don't matter.
+pub(crate) const FLOATING_EQ_RANGE: f64 = 0.001;
 
+// How numbers are compared by `eq_with`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Numbers {
+    // Only the same number is equal, though an integer and a float can be: `2` equals `2.0`.
+    Exact,
+    // Numbers are equal if they are less than `FLOATING_EQ_RANGE` apart.
+    Close,
+}
+
+// Structural equality, which is what `=` uses:
+//  - numbers are equal if they have exactly the same value, so `2` and `2.0` are equal but `1.0005`
+//    and `1.0` aren't;
+//  - lists are equal if they have the same length and each pair of elements is equal;
+//  - functions and promises are never equal.
 impl PartialEq for LispType {
     fn eq(&self, other: &Self) -> bool {
+        self.eq_with(other, Numbers::Exact, &mut Vec::new())
+    }
+}
+
+impl LispType {
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            LispType::Integer(_) => "integer",
+            LispType::Str(_) => "string",
+            LispType::Func(_) => "function",
+            LispType::Statement(_) => "statement",
+            LispType::List(_) => "list",
+            LispType::Floating(_) => "float",
+            LispType::Bool(_) => "bool",
+            LispType::Char(_) => "char",
+            LispType::Promise(_) => "promise",
+            LispType::Symbol(_) => "symbol",
+            LispType::Nil => "nil",
+        }
+    }
+    // The same as `==`, except that numbers (even inside lists) only have to be close together.
+    // This is what `deep-equal` uses.
+    pub(crate) fn deep_eq(&self, other: &Self) -> bool {
+        self.eq_with(other, Numbers::Close, &mut Vec::new())
+    }
+    // `seen` holds the pairs of values that are already being compared further up, so that lists
+    // which contain themselves don't recurse forever.
+    pub(crate) fn eq_with(&self, other: &Self, numbers: Numbers, seen: &mut SeenPairs) -> bool {
         match (self, other) {
             (&LispType::Integer(lhs), &LispType::Integer(rhs)) => lhs == rhs,
             (LispType::Str(lhs), LispType::Str(rhs)) => lhs == rhs,
             (LispType::Statement(lhs), LispType::Statement(rhs)) => lhs == rhs,
             (LispType::Func(_), LispType::Func(_)) => false,
+            (LispType::Promise(_), LispType::Promise(_)) => false,
+            (LispType::Symbol(lhs), LispType::Symbol(rhs)) => lhs == rhs,
             (LispType::Nil, LispType::Nil) => true,
-            (LispType::Floating(lhs), LispType::Floating(rhs)) => {
-                (lhs - rhs).abs() < FLOATING_EQ_RANGE
+            (LispType::Bool(lhs), LispType::Bool(rhs)) => lhs == rhs,
+            (LispType::Char(lhs), LispType::Char(rhs)) => lhs == rhs,
+            // Two integers were already compared exactly, so at least one of these is a float.
+            (lhs, rhs) if lhs.as_f64().is_some() && rhs.as_f64().is_some() => {
+                let (lhs, rhs) = (lhs.as_f64().unwrap(), rhs.as_f64().unwrap());
+                match numbers {
+                    Numbers::Exact => lhs == rhs,
+                    Numbers::Close => (lhs - rhs).abs() < FLOATING_EQ_RANGE,
+                }
+            }
+            (LispType::List(lhs), LispType::List(rhs)) => {
+                lhs.len() == rhs.len()
+                    && lhs
+                        .iter()
+                        .zip(rhs.iter())
+                        .all(|(l, r)| l.eq_with(r, numbers, seen))
             }
-            (LispType::List(lhs), LispType::List(rhs)) => lhs == rhs,
-            // TODOO(#10): Comparing floats and integers
             _ => false,
         }
     }
-}
-
-impl LispType {
+    // The value of a number as a float, so that integers and floats can be used together.
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match *self {
+            LispType::Integer(i) => Some(i as f64),
+            LispType::Floating(f) => Some(f),
+            _ => None,
+        }
+    }
+    // `nil`, `false` and `0` are false, everything else is true.
+    pub(crate) fn is_truthy(&self) -> bool {
+        !matches!(
+            self,
+            LispType::Nil | LispType::Bool(false) | LispType::Integer(0)
+        )
+    }
     pub(crate) fn unwrap_func(&self) -> &dyn Callable {
         match self {
             LispType::Func(f) => f.as_ref(),
@@ -60,6 +143,18 @@ impl LispType {
 
 impl Display for LispType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_with(f, &mut Vec::new())
+    }
+}
+
+impl LispType {
+    // `seen` holds the values that are already being displayed further up, so that lists which
+    // contain themselves print `...` instead of recursing forever.
+    pub(crate) fn fmt_with(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        seen: &mut Vec<*const RefCell<LispType>>,
+    ) -> std::fmt::Result {
         match self {
             LispType::Integer(i) => write!(f, "{i}"),
             LispType::Str(s) => write!(f, "{s}"),
@@ -69,13 +164,22 @@ impl Display for LispType {
                 Err(e) => write!(f, "{e}"),
             },
             LispType::List(l) => {
-                let mut t = String::new();
-                for item in l {
-                    t = format!("{t} {item}");
+                write!(f, "(")?;
+                for (i, item) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    item.fmt_with(f, seen)?;
                 }
-                write!(f, "({t})")
+                write!(f, ")")
             }
+            // Whole floats keep their `.0`, so that they don't look like integers.
+            LispType::Floating(fl) if fl.is_finite() && fl.fract() == 0.0 => write!(f, "{fl}.0"),
             LispType::Floating(fl) => write!(f, "{fl}"),
+            LispType::Bool(b) => write!(f, "{b}"),
+            LispType::Char(c) => write!(f, "{c}"),
+            LispType::Promise(_) => write!(f, "<Promise>"),
+            LispType::Symbol(name) => write!(f, "{name}"),
             LispType::Nil => write!(f, "nil"),
         }
     }
@@ -98,7 +202,7 @@ impl From<&str> for LispType {
 }
 impl<T: Callable + 'static> From<T> for LispType {
     fn from(i: T) -> Self {
-        LispType::Func(Box::new(i))
+        LispType::Func(Rc::new(i))
     }
 }
 impl From<Statement> for LispType {
@@ -106,8 +210,23 @@ impl From<Statement> for LispType {
         LispType::Statement(i)
     }
 }
+impl From<Promise> for LispType {
+    fn from(i: Promise) -> Self {
+        LispType::Promise(i)
+    }
+}
 impl From<f64> for LispType {
     fn from(i: f64) -> Self {
         LispType::Floating(i)
     }
 }
+impl From<bool> for LispType {
+    fn from(i: bool) -> Self {
+        LispType::Bool(i)
+    }
+}
+impl From<char> for LispType {
+    fn from(i: char) -> Self {
+        LispType::Char(i)
+    }
+}
@@ -0,0 +1,182 @@
+use pale::{
+    make_ast, run_lisp, tokenize, Arity, Callable, LispErrors, LispType, Location, MultiMethod,
+    ParseError, ReadLine, Scope, TimeNow, Var,
+};
+use std::cell::RefCell;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn start() -> Location {
+    Location {
+        filename: "test.lisp".to_string(),
+        line: 0,
+        col: 0,
+    }
+}
+
+fn run_in(source: &str, scope: &mut Scope) -> Result<Var, LispErrors> {
+    let toks = tokenize(source, "test.lisp".to_string())?;
+    make_ast(&toks, scope, &start())?.resolve()
+}
+
+#[test]
+fn run_lisp_gives_back_a_value() {
+    let res = run_lisp("(+ 1 2)", "test.lisp").unwrap();
+    assert_eq!(*res.get(), LispType::Integer(3));
+    assert_eq!(res.to_string(), "3");
+}
+
+#[test]
+fn errors_can_be_boxed() -> Result<(), Box<dyn std::error::Error>> {
+    let res = run_lisp("(* 2 3)", "test.lisp")?;
+    assert_eq!(res.to_string(), "6");
+    let err: Box<dyn std::error::Error> = run_lisp("(+ 1 \"x\")", "test.lisp").unwrap_err().into();
+    assert!(err.to_string().contains("Incompatible types"));
+    Ok(())
+}
+
+#[test]
+fn parse_and_run_in_a_scope() -> Result<(), LispErrors> {
+    let mut scope = Scope::default();
+    let start = start();
+    let toks = tokenize("(let x 5)", "test.lisp".to_string())?;
+    make_ast(&toks, &mut scope, &start)?.resolve()?;
+    let toks = tokenize("(* x x)", "test.lisp".to_string())?;
+    let res: Var = make_ast(&toks, &mut scope, &start)?.resolve()?;
+    assert_eq!(*res.get(), LispType::Integer(25));
+    Ok(())
+}
+
+#[test]
+fn register_a_native_function() -> Result<(), LispErrors> {
+    #[derive(Debug, Clone)]
+    struct Answer;
+    impl Callable for Answer {
+        fn arity(&self) -> Arity {
+            Arity::exactly(0)
+        }
+        fn call(&self, _args: &[Var], _loc_called: &Location) -> Result<Var, LispErrors> {
+            Ok(Var::new(42))
+        }
+    }
+    let mut scope = Scope::default();
+    scope.register("myfunc", Answer);
+    let toks = tokenize("(+ (myfunc) 1)", "test.lisp".to_string())?;
+    let res = make_ast(&toks, &mut scope, &start())?.resolve()?;
+    assert_eq!(*res.get(), LispType::Integer(43));
+    Ok(())
+}
+
+#[test]
+fn parse_errors_can_be_matched() {
+    let err = run_lisp("(+ 1 nope)", "test.lisp").unwrap_err();
+    match err.parse_errors().next() {
+        Some(ParseError::UnknownIdent { name, loc }) => {
+            assert_eq!(name, "nope");
+            assert_eq!(loc.col, 5);
+        }
+        other => panic!("Expected an unknown identifier, got {other:?}"),
+    }
+    let err = run_lisp("(+ 1 (- 2 3", "test.lisp").unwrap_err();
+    assert!(matches!(
+        err.parse_errors().next(),
+        Some(ParseError::UnmatchedParen { paren: '(', .. })
+    ));
+    assert!(err.to_string().contains("Unmatched opening parentheses!"));
+    // Type errors aren't parse errors.
+    let err = run_lisp("(+ 1 \"x\")", "test.lisp").unwrap_err();
+    assert_eq!(err.parse_errors().count(), 0);
+}
+
+#[test]
+fn empty_scopes_have_no_builtins() {
+    let toks = tokenize("(print 1)", "test.lisp".to_string()).unwrap();
+    let err = make_ast(&toks, &mut Scope::empty(), &start()).unwrap_err();
+    assert!(
+        err.to_string().contains("Unknown identifier `print`"),
+        "{err}"
+    );
+
+    let mut scope = Scope::with_builtins(&["+", "not-a-builtin"]);
+    let toks = tokenize("(+ 1 2)", "test.lisp".to_string()).unwrap();
+    let ast = make_ast(&toks, &mut scope, &start()).unwrap();
+    assert_eq!(*ast.resolve().unwrap().get(), LispType::Integer(3));
+    let toks = tokenize("(* 1 2)", "test.lisp".to_string()).unwrap();
+    assert!(make_ast(&toks, &mut scope, &start()).is_err());
+}
+
+#[test]
+fn hosts_can_set_the_clock() -> Result<(), LispErrors> {
+    let mut scope = Scope::default();
+    scope.register(
+        "time-now",
+        TimeNow::with_clock(|| UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+    );
+    let res = run_in("(+ (time-now) 1)", &mut scope)?;
+    assert_eq!(*res.get(), LispType::Integer(1_700_000_001));
+    Ok(())
+}
+
+#[test]
+fn hosts_can_supply_input() -> Result<(), LispErrors> {
+    let mut scope = Scope::default();
+    scope.register(
+        "read-line",
+        ReadLine::with_reader(Cursor::new("one\ntwo\n")),
+    );
+    assert_eq!(run_in("(read-line)", &mut scope)?.to_string(), "one");
+    assert_eq!(run_in("(read-line)", &mut scope)?.to_string(), "two");
+    assert_eq!(*run_in("(read-line)", &mut scope)?.get(), LispType::Nil);
+    Ok(())
+}
+
+#[test]
+fn multimethods_dispatch_on_the_first_argument() -> Result<(), LispErrors> {
+    #[derive(Debug, Clone)]
+    struct Describe(&'static str);
+    impl Callable for Describe {
+        fn call(&self, args: &[Var], _loc_called: &Location) -> Result<Var, LispErrors> {
+            Ok(Var::new(format!("{} {}", self.0, args[0])))
+        }
+    }
+    let mut describe = MultiMethod::new();
+    describe.register("integer", Describe("the integer"));
+    describe.register("string", Describe("the string"));
+    let mut scope = Scope::default();
+    scope.register("describe", describe);
+    let res = run_in("(describe (+ 1 1))", &mut scope)?;
+    assert_eq!(res.to_string(), "the integer 2");
+    let res = run_in("(describe \"hi\")", &mut scope)?;
+    assert_eq!(res.to_string(), "the string hi");
+    let err = run_in("(describe 1.5)", &mut scope).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("No implementation for arguments of type `float`"));
+    Ok(())
+}
+
+#[test]
+fn hosts_can_capture_test_results() -> Result<(), LispErrors> {
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let out = Shared::default();
+    let mut scope = Scope::with_test_output(out.clone());
+    run_in("(deftest \"fails\" (assert false \"nope\"))", &mut scope)?;
+    assert_eq!(
+        *run_in("(run-tests)", &mut scope)?.get(),
+        LispType::Integer(1)
+    );
+    let printed = String::from_utf8(out.0.take()).unwrap();
+    assert!(printed.starts_with("FAIL fails: "), "{printed}");
+    assert!(printed.ends_with("0 passed, 1 failed\n"), "{printed}");
+    Ok(())
+}